@@ -0,0 +1,164 @@
+//! Compares how much of a file each metadata reading strategy pulls from disk.
+//!
+//! Builds large sparse copies of the RAW, JPEG and video fixtures (the image or media data
+//! is a hole of `CHRONOPHOTO_BENCH_SIZE_MB` megabytes, 512 by default) and dates each of
+//! them three ways: reading the whole file, `exif::Reader::read_from_container` (which
+//! chronophoto used for TIFF-based RAW and JPEG, and which gives up early on formats it
+//! does not know) and a dry run of `chronophoto::processor::process`.
+//!
+//! Bytes are taken from `rchar` in `/proc/self/io`, so they are only reported on Linux.
+//! Files sit in the page cache, so times show the cost of copying data, not of the disk.
+//!
+//!     cargo bench --bench header_read
+
+use chronophoto::processor::process;
+use chronophoto::types::{Action, Args};
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+const MIB: u64 = 1024 * 1024;
+
+fn main() {
+    let size = env::var("CHRONOPHOTO_BENCH_SIZE_MB")
+        .ok()
+        .and_then(|mb| mb.parse().ok())
+        .unwrap_or(512)
+        * MIB;
+    let work = tempfile::tempdir().expect("temp dir should be created");
+
+    if bytes_read().is_none() {
+        println!("/proc/self/io is not available: bytes read are reported as n/a\n");
+    }
+    println!(
+        "{:<20} {:>10}  {:>22}  {:>22}  {:>22}",
+        "file", "size", "full read", "read_from_container", "chronophoto"
+    );
+
+    for name in [
+        "raw_nikon.nef",
+        "raw_sony.arw",
+        "raw_canon.cr2",
+        "raw_adobe.dng",
+        "raw_olympus.orf",
+        "raw_fuji.raf",
+        "raw_canon.cr3",
+        "photo_2025_06_15.jpg",
+        "video_2025_07_14.mp4",
+    ] {
+        let dir = work.path().join(name.replace('.', "_"));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        if name.ends_with(".mp4") {
+            grow_mdat(&Path::new(FIXTURES).join(name), &path, size);
+        } else {
+            fs::copy(Path::new(FIXTURES).join(name), &path).unwrap();
+            // Image data past the metadata, as in a real RAW or a large JPEG
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_len(size)
+                .unwrap();
+        }
+
+        let full = measure(|| {
+            fs::read(&path).unwrap();
+        });
+        let container = measure(|| {
+            let file = File::open(&path).unwrap();
+            let _ = exif::Reader::new().read_from_container(&mut BufReader::new(file));
+        });
+        let bounded = measure(|| {
+            let summary = process(dry_run_args(dir.clone(), work.path().join("library"))).unwrap();
+            assert!(
+                summary.contains("1 would be transferred"),
+                "{} was not dated: {}",
+                name,
+                summary
+            );
+        });
+
+        println!(
+            "{:<20} {:>10}  {:>22}  {:>22}  {:>22}",
+            name,
+            format_bytes(fs::metadata(&path).unwrap().len()),
+            full,
+            container,
+            bounded
+        );
+    }
+}
+
+/// Rewrites an MP4 with its `mdat` grown to `size` bytes, keeping `moov` after it
+fn grow_mdat(source: &Path, destination: &Path, size: u64) {
+    let data = fs::read(source).unwrap();
+    let mut output = File::create(destination).unwrap();
+
+    let mut offset = 0;
+    while offset + 8 <= data.len() {
+        let atom_size = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        let atom = &data[offset..offset + atom_size];
+        if &atom[4..8] == b"mdat" {
+            output.write_all(&1u32.to_be_bytes()).unwrap();
+            output.write_all(b"mdat").unwrap();
+            output.write_all(&size.to_be_bytes()).unwrap();
+            output.seek(SeekFrom::Current(size as i64 - 16)).unwrap();
+        } else {
+            output.write_all(atom).unwrap();
+        }
+        offset += atom_size;
+    }
+}
+
+fn dry_run_args(source: PathBuf, library: PathBuf) -> Args {
+    Args {
+        action: Action::Copy,
+        dry_run: true,
+        log_file: Some(env::temp_dir().join("chronophoto-bench.log")),
+        threads: 1,
+        ..Args::new(vec![source], library)
+    }
+}
+
+/// Runs `read` and returns the bytes it read and the time it took
+fn measure(read: impl FnOnce()) -> String {
+    let before = bytes_read();
+    let started = Instant::now();
+    read();
+    let elapsed = started.elapsed();
+    let bytes = match (before, bytes_read()) {
+        (Some(before), Some(after)) => format_bytes(after - before),
+        _ => "n/a".to_string(),
+    };
+    format!("{} {}", bytes, format_duration(elapsed))
+}
+
+/// Bytes this process has read so far through `read` and similar system calls
+fn bytes_read() -> Option<u64> {
+    fs::read_to_string("/proc/self/io")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("rchar:"))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+fn format_bytes(bytes: u64) -> String {
+    if bytes >= MIB {
+        format!("{:.1} MiB", bytes as f64 / MIB as f64)
+    } else if bytes >= 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("({:.1} ms)", duration.as_secs_f64() * 1000.0)
+}
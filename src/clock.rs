@@ -0,0 +1,139 @@
+use crate::types::{ClockCorrection, PhotoDateTime};
+use chrono::{NaiveDate, TimeDelta};
+use log::debug;
+
+/// Camera that took a photo, from EXIF `Make`, `Model` and `BodySerialNumber`
+#[derive(Debug, Default)]
+pub(crate) struct Camera {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+}
+
+impl ClockCorrection {
+    fn applies_to(&self, camera: &Camera, date: NaiveDate) -> bool {
+        let matches = |expected: &Option<String>, actual: &Option<String>| match expected {
+            None => true,
+            Some(expected) => actual
+                .as_ref()
+                .is_some_and(|actual| expected.eq_ignore_ascii_case(actual)),
+        };
+
+        matches(&self.make, &camera.make)
+            && matches(&self.model, &camera.model)
+            && matches(&self.serial, &camera.serial)
+            && self.from.is_none_or(|from| date >= from)
+            && self.until.is_none_or(|until| date <= until)
+    }
+}
+
+/// Shifts the recorded time by the first correction matching the camera and recorded date
+pub(crate) fn correct_clock(
+    dt: PhotoDateTime,
+    camera: &Camera,
+    corrections: &[ClockCorrection],
+) -> PhotoDateTime {
+    match corrections
+        .iter()
+        .find(|correction| correction.applies_to(camera, dt.date()))
+    {
+        Some(correction) => {
+            debug!(
+                "Correcting clock of {:?} by {} seconds",
+                camera, correction.shift
+            );
+            dt.shifted(TimeDelta::seconds(correction.shift))
+        }
+        None => dt,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(make: &str, model: &str, serial: Option<&str>) -> Camera {
+        Camera {
+            make: Some(make.to_string()),
+            model: Some(model.to_string()),
+            serial: serial.map(str::to_string),
+        }
+    }
+
+    fn photo_datetime(year: u16, month: u8, day: u8, hour: u8, minute: u8) -> PhotoDateTime {
+        PhotoDateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second: 0,
+            offset: None,
+            nanosecond: None,
+        }
+    }
+
+    fn ymd_hms(dt: PhotoDateTime) -> (u16, u8, u8, u8, u8, u8) {
+        (dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second)
+    }
+
+    fn correction(s: &str) -> ClockCorrection {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_rolls_over_year() {
+        let dt = correct_clock(
+            photo_datetime(2024, 12, 31, 23, 30),
+            &camera("Canon", "Canon EOS R6", None),
+            &[correction("canon,canon eos r6,,+01:12")],
+        );
+        assert_eq!(ymd_hms(dt), (2025, 1, 1, 0, 42, 0));
+    }
+
+    #[test]
+    fn test_negative_shift_rolls_back_month() {
+        let dt = correct_clock(
+            photo_datetime(2024, 3, 1, 0, 10),
+            &camera("Nikon", "Z 6", None),
+            &[correction("Nikon,,,-00:15:30")],
+        );
+        assert_eq!(ymd_hms(dt), (2024, 2, 29, 23, 54, 30));
+    }
+
+    #[test]
+    fn test_first_match_wins() {
+        let dt = correct_clock(
+            photo_datetime(2025, 6, 5, 12, 0),
+            &camera("Canon", "Canon EOS R6", Some("0320")),
+            &[
+                correction("Canon,,0999,+02:00"),
+                correction("Canon,,0320,+00:10"),
+                correction("*,*,*,+01:00"),
+            ],
+        );
+        assert_eq!(ymd_hms(dt), (2025, 6, 5, 12, 10, 0));
+    }
+
+    #[test]
+    fn test_date_range() {
+        let corrections = [correction("Canon,,,+01:00,2025-06-01,2025-06-14")];
+        let canon = camera("Canon", "Canon EOS R6", None);
+
+        let inside = correct_clock(photo_datetime(2025, 6, 14, 22, 0), &canon, &corrections);
+        assert_eq!(ymd_hms(inside), (2025, 6, 14, 23, 0, 0));
+
+        let outside = correct_clock(photo_datetime(2025, 6, 15, 8, 0), &canon, &corrections);
+        assert_eq!(ymd_hms(outside), (2025, 6, 15, 8, 0, 0));
+    }
+
+    #[test]
+    fn test_unknown_camera() {
+        let dt = correct_clock(
+            photo_datetime(2025, 6, 5, 12, 0),
+            &Camera::default(),
+            &[correction("Canon,,,+01:00")],
+        );
+        assert_eq!(ymd_hms(dt), (2025, 6, 5, 12, 0, 0));
+    }
+}
@@ -1,10 +1,113 @@
-use log::error;
-use std::path::PathBuf;
-use walkdir::WalkDir;
+use crate::setup::{is_library, nested_library};
+use crate::takeout::is_takeout_json;
+use crate::types::{Args, MEDIA_EXTENSIONS};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use log::{debug, error, warn};
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use walkdir::{DirEntry, WalkDir};
 
-pub fn discover_files(root: PathBuf) -> Vec<PathBuf> {
-    WalkDir::new(root)
+/// Gitignore-style file listing what to leave out of its folder and everything below it
+pub const IGNORE_FILE_NAME: &str = ".chronophotoignore";
+
+/// Folders NAS devices and operating systems keep thumbnails, trash and indexes in.
+/// Thumbnails such as Synology's `@eaDir` carry the original EXIF and would be organized
+/// as photos.
+const SYSTEM_DIRECTORIES: &[&str] = &[
+    "@eaDir",
+    "#recycle",
+    "#snapshot",
+    ".@__thumb",
+    "@Recycle",
+    "@Recently-Snapshot",
+    ".AppleDouble",
+    ".DocumentRevisions-V100",
+    ".fseventsd",
+    ".Spotlight-V100",
+    ".TemporaryItems",
+    ".Trashes",
+    ".thumbnails",
+    "$RECYCLE.BIN",
+    "System Volume Information",
+];
+
+/// Walks the sources one directory at a time, leaving out a library nested inside them,
+/// system folders and what the filters and `.chronophotoignore` files exclude. Excluded
+/// folders are not entered. Symlinks, hidden files and depth follow `args`; when following
+/// symlinks, a link back to a folder being walked is reported as an error and skipped.
+///
+/// Files are sorted by name and come before subdirectories, so every directory yields one
+/// batch holding all of its files: photos can be grouped with their companions without
+/// collecting the whole tree first. Several sources are walked side by side and merged as
+/// if they had been copied into one folder: folders at the same relative path share a batch.
+///
+/// When the library is reorganized in place, `placed` tells the walk what this run has
+/// already put there.
+pub fn discover_batches<'a>(
+    args: &'a Args,
+    placed: Option<&'a Placed>,
+) -> impl Iterator<Item = Vec<PathBuf>> + 'a {
+    let walks = args
+        .sources
+        .iter()
+        .map(|source| {
+            let placed = placed.filter(|_| is_library(source, args));
+            walk_source(source, args, placed).peekable()
+        })
+        .collect();
+
+    DirectoryBatches {
+        files: MergedWalks { walks }.peekable(),
+    }
+}
+
+/// Wanted files of one source in walk order, each with its path relative to the source
+fn walk_source<'a>(
+    source: &'a Path,
+    args: &'a Args,
+    placed: Option<&'a Placed>,
+) -> impl Iterator<Item = (PathBuf, PathBuf)> + 'a {
+    let mut ignores = IgnoreStack::default();
+    let library = nested_library(source, args);
+    let follow_symlinks = args.follow_symlinks;
+    let mut walker = WalkDir::new(source).follow_links(follow_symlinks);
+    if let Some(max_depth) = args.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+
+    walker
+        .sort_by(move |a, b| {
+            (is_walked_dir(a, follow_symlinks), a.file_name())
+                .cmp(&(is_walked_dir(b, follow_symlinks), b.file_name()))
+        })
         .into_iter()
+        .filter_entry(move |e| {
+            if e.depth() > 0 && args.skip_hidden && e.file_name().to_string_lossy().starts_with('.')
+            {
+                return false;
+            }
+            if ignores.is_ignored(e) {
+                return false;
+            }
+            if e.file_type().is_dir() {
+                if library.as_deref() == Some(e.path()) {
+                    debug!("Skipped the library {}", e.path().display());
+                    return false;
+                }
+                if e.depth() > 0 && (is_system_directory(e, args) || is_excluded(e, source, args)) {
+                    return false;
+                }
+                if placed.is_some_and(|placed| placed.is_created(relative_path(e, source))) {
+                    debug!("Skipped folder created by this run {}", e.path().display());
+                    return false;
+                }
+                ignores.enter(e.path());
+            }
+            true
+        })
         .filter_map(|e| match e {
             Ok(entry) => Some(entry),
             Err(err) => {
@@ -12,7 +115,573 @@ pub fn discover_files(root: PathBuf) -> Vec<PathBuf> {
                 None
             }
         })
-        .filter(|e| e.file_type().is_file())
-        .map(|e| e.into_path())
-        .collect()
+        .filter(move |e| e.file_type().is_file() && is_wanted(e, source, args))
+        .map(move |e| (relative_path(&e, source).to_path_buf(), e.into_path()))
+        .filter(move |(relative, _)| placed.is_none_or(|placed| placed.pass(relative)))
+}
+
+/// What this run has put into the library while walking it as a source, so the walk does
+/// not read it again: folders the run created, and files moved into existing folders the
+/// walk has yet to list. Files are forgotten once the walk passes them, so only the new
+/// folders and the files waiting ahead of the walk are kept.
+pub struct Placed {
+    library: PathBuf,
+    state: Mutex<PlacedState>,
+}
+
+#[derive(Default)]
+struct PlacedState {
+    /// Last file the walk yielded, relative to the library
+    position: Option<PathBuf>,
+    folders: HashSet<PathBuf>,
+    files: HashSet<PathBuf>,
+}
+
+impl Placed {
+    pub fn new(library: &Path) -> Self {
+        Self {
+            library: library.to_path_buf(),
+            state: Mutex::default(),
+        }
+    }
+
+    /// Records a file about to be written to `destination`, before its folder is created
+    pub fn record(&self, destination: &Path) {
+        let Ok(relative) = destination.strip_prefix(&self.library) else {
+            return;
+        };
+        let mut state = self
+            .state
+            .lock()
+            .expect("placed state should not be poisoned");
+
+        // A folder the write creates holds nothing but placed files
+        let created = relative
+            .ancestors()
+            .skip(1)
+            .take_while(|folder| {
+                !folder.as_os_str().is_empty() && !self.library.join(folder).exists()
+            })
+            .last();
+        if let Some(folder) = created {
+            state.folders.insert(folder.to_path_buf());
+            return;
+        }
+
+        let in_created = relative
+            .ancestors()
+            .skip(1)
+            .any(|folder| state.folders.contains(folder));
+        if !in_created && state.is_ahead(relative) {
+            state.files.insert(relative.to_path_buf());
+        }
+    }
+
+    fn is_created(&self, relative: &Path) -> bool {
+        let state = self
+            .state
+            .lock()
+            .expect("placed state should not be poisoned");
+        state.folders.contains(relative)
+    }
+
+    /// Moves the walk past a file; `false` when this run placed it
+    fn pass(&self, relative: &Path) -> bool {
+        let mut state = self
+            .state
+            .lock()
+            .expect("placed state should not be poisoned");
+        state.position = Some(relative.to_path_buf());
+        let placed = state.files.remove(relative);
+        if placed {
+            debug!("Skipped file placed by this run {}", relative.display());
+        }
+        !placed
+    }
+}
+
+impl PlacedState {
+    /// Whether the walk has yet to list the folder of a file
+    fn is_ahead(&self, relative: &Path) -> bool {
+        let Some(position) = &self.position else {
+            return true;
+        };
+        // The folders leading to the current file were listed when the walk entered them
+        let folder = relative.parent().unwrap_or(Path::new(""));
+        if position.starts_with(folder) {
+            return false;
+        }
+        walk_order(relative) > walk_order(position)
+    }
+}
+
+/// The `.chronophotoignore` files of the folder being walked and its ancestors
+#[derive(Default)]
+struct IgnoreStack {
+    ignores: Vec<Gitignore>,
+}
+
+impl IgnoreStack {
+    /// Whether the deepest ignore file with a rule for the entry excludes it; a `!pattern`
+    /// rule in a subfolder brings back what a parent folder excluded
+    fn is_ignored(&mut self, entry: &DirEntry) -> bool {
+        // The walk is depth-first, so ignore files of folders it has left are on top
+        let parent = entry.path().parent().unwrap_or(entry.path());
+        while self
+            .ignores
+            .last()
+            .is_some_and(|ignore| !parent.starts_with(ignore.path()))
+        {
+            self.ignores.pop();
+        }
+
+        if entry.depth() == 0 {
+            return false;
+        }
+        if entry.file_name() == IGNORE_FILE_NAME {
+            return true;
+        }
+
+        let is_dir = entry.file_type().is_dir();
+        for ignore in self.ignores.iter().rev() {
+            let matched = ignore.matched(entry.path(), is_dir);
+            if matched.is_ignore() {
+                debug!("Ignored {}", entry.path().display());
+                return true;
+            }
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+        false
+    }
+
+    /// Loads the ignore file of a folder about to be walked, if it has one
+    fn enter(&mut self, dir: &Path) {
+        let path = dir.join(IGNORE_FILE_NAME);
+        if !path.is_file() {
+            return;
+        }
+
+        let mut builder = GitignoreBuilder::new(dir);
+        if let Some(err) = builder.add(&path) {
+            warn!("Failed to read part of {}: {}", path.display(), err);
+        }
+        match builder.build() {
+            Ok(ignore) => self.ignores.push(ignore),
+            Err(err) => warn!("Failed to read {}: {}", path.display(), err),
+        }
+    }
+}
+
+/// Whether the walk descends into the entry. While sorting, a followed symlink still has
+/// the file type of the link, yet it must come after the files of its folder like any
+/// other subfolder.
+fn is_walked_dir(entry: &DirEntry, follow_symlinks: bool) -> bool {
+    entry.file_type().is_dir()
+        || (follow_symlinks && entry.path_is_symlink() && entry.path().is_dir())
+}
+
+fn relative_path<'a>(entry: &'a DirEntry, source: &Path) -> &'a Path {
+    entry.path().strip_prefix(source).unwrap_or(entry.path())
+}
+
+fn is_system_directory(entry: &DirEntry, args: &Args) -> bool {
+    if args.include_system_dirs {
+        return false;
+    }
+    let name = entry.file_name().to_string_lossy();
+    let system = SYSTEM_DIRECTORIES
+        .iter()
+        .any(|dir| dir.eq_ignore_ascii_case(&name));
+    if system {
+        debug!("Skipped system folder {}", entry.path().display());
+    }
+    system
+}
+
+fn is_excluded(entry: &DirEntry, source: &Path, args: &Args) -> bool {
+    let relative = relative_path(entry, source);
+    let excluded = args
+        .exclude
+        .iter()
+        .any(|pattern| pattern.is_match(relative));
+    if excluded {
+        debug!("Excluded {}", entry.path().display());
+    }
+    excluded
+}
+
+fn is_wanted(entry: &DirEntry, source: &Path, args: &Args) -> bool {
+    let extension = entry
+        .path()
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    let reads_all = args.extensions.as_ref().is_some_and(Vec::is_empty);
+    let extension_allowed = match &extension {
+        Some(ext) => {
+            let listed = match &args.extensions {
+                Some(extensions) => reads_all || extensions.contains(ext),
+                None => MEDIA_EXTENSIONS.contains(&ext.as_str()),
+            };
+            listed && !args.exclude_extensions.contains(ext)
+        }
+        // Dot files like `.DS_Store` are OS metadata, but photos exported without an
+        // extension still carry EXIF
+        None => reads_all || !entry.file_name().to_string_lossy().starts_with('.'),
+    };
+    if !extension_allowed {
+        debug!("Skipped by extension {}", entry.path().display());
+        return false;
+    }
+    // Takeout exports hold album and account JSON that belongs to no photo
+    if extension.as_deref() == Some("json") && !is_takeout_json(entry.path()) {
+        debug!("Skipped JSON without a photo {}", entry.path().display());
+        return false;
+    }
+
+    let relative = relative_path(entry, source);
+    if !args.include.is_empty()
+        && !args
+            .include
+            .iter()
+            .any(|pattern| pattern.is_match(relative))
+    {
+        debug!("Not included {}", entry.path().display());
+        return false;
+    }
+    !is_excluded(entry, source, args)
+}
+
+/// Position of a file in the walk: the folders leading to it, then the file itself, which
+/// sorts before the subfolders of its folder
+fn walk_order(relative: &Path) -> Vec<(bool, &OsStr)> {
+    let mut order: Vec<_> = relative.iter().map(|name| (true, name)).collect();
+    if let Some(file) = order.last_mut() {
+        file.0 = false;
+    }
+    order
+}
+
+/// Interleaves the walks of several sources into the order a walk of their union would
+/// have; a file present in several sources comes first from the source given first
+struct MergedWalks<I: Iterator<Item = (PathBuf, PathBuf)>> {
+    walks: Vec<Peekable<I>>,
+}
+
+impl<I: Iterator<Item = (PathBuf, PathBuf)>> Iterator for MergedWalks<I> {
+    type Item = (PathBuf, PathBuf);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (index, _) = self
+            .walks
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, walk)| {
+                walk.peek()
+                    .map(|(relative, _)| (index, walk_order(relative)))
+            })
+            .min_by(|(_, a), (_, b)| a.cmp(b))?;
+        self.walks[index].next()
+    }
+}
+
+/// Groups files by their folder relative to the source, so same-named folders of
+/// different sources form one batch
+struct DirectoryBatches<I: Iterator<Item = (PathBuf, PathBuf)>> {
+    files: Peekable<I>,
+}
+
+impl<I: Iterator<Item = (PathBuf, PathBuf)>> Iterator for DirectoryBatches<I> {
+    type Item = Vec<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (relative, first) = self.files.next()?;
+        let dir = relative.parent().map(PathBuf::from);
+        let mut batch = vec![first];
+        while let Some((_, path)) = self
+            .files
+            .next_if(|(relative, _)| relative.parent().map(PathBuf::from) == dir)
+        {
+            batch.push(path);
+        }
+        Some(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn create_test_args(source: &Path) -> Args {
+        Args {
+            extensions: Some(vec![]),
+            ..Args::new(vec![source.to_path_buf()], PathBuf::from("library"))
+        }
+    }
+
+    /// Discovered files relative to the root, one vector per batch
+    fn discover(root: &Path, args: &Args) -> Vec<Vec<PathBuf>> {
+        discover_batches(args, None)
+            .map(|batch| {
+                batch
+                    .into_iter()
+                    .map(|path| path.strip_prefix(root).unwrap().to_path_buf())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_one_batch_per_directory() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("a/nested")).unwrap();
+        fs::create_dir_all(root.path().join("empty")).unwrap();
+        for name in [
+            "z.jpg",
+            "a/IMG_2.JPG",
+            "a/nested/x.jpg",
+            "a/IMG_1.JPG",
+            "b.jpg",
+        ] {
+            fs::write(root.path().join(name), "").unwrap();
+        }
+
+        let args = create_test_args(root.path());
+        assert_eq!(
+            discover(root.path(), &args),
+            vec![
+                vec![PathBuf::from("b.jpg"), PathBuf::from("z.jpg")],
+                vec![PathBuf::from("a/IMG_1.JPG"), PathBuf::from("a/IMG_2.JPG")],
+                vec![PathBuf::from("a/nested/x.jpg")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multiple_sources() {
+        let card1 = tempfile::tempdir().unwrap();
+        let card2 = tempfile::tempdir().unwrap();
+        for (root, names) in [
+            (&card1, ["b.jpg", "DCIM/x.jpg", "DCIM/z.jpg"]),
+            (&card2, ["a.jpg", "DCIM/y.jpg", "Other/w.jpg"]),
+        ] {
+            for name in names {
+                let path = root.path().join(name);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, "").unwrap();
+            }
+        }
+
+        let mut args = create_test_args(card1.path());
+        args.sources.push(card2.path().to_path_buf());
+        let batches: Vec<Vec<PathBuf>> = discover_batches(&args, None).collect();
+        assert_eq!(
+            batches,
+            vec![
+                vec![card2.path().join("a.jpg"), card1.path().join("b.jpg")],
+                vec![
+                    card1.path().join("DCIM/x.jpg"),
+                    card2.path().join("DCIM/y.jpg"),
+                    card1.path().join("DCIM/z.jpg"),
+                ],
+                vec![card2.path().join("Other/w.jpg")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filters() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("2024-06 Rome/cache")).unwrap();
+        fs::create_dir_all(root.path().join("2023")).unwrap();
+        for name in [
+            ".DS_Store",
+            "notes.txt",
+            "exported",
+            "a.JPG",
+            "a.tmp.jpg",
+            "2024-06 Rome/IMG_1.CR3",
+            "2024-06 Rome/IMG_1.xmp",
+            "2024-06 Rome/cache/thumb.jpg",
+            "2023/IMG_0.jpg",
+        ] {
+            fs::write(root.path().join(name), "").unwrap();
+        }
+
+        let mut args = create_test_args(root.path());
+        args.extensions = Some(vec![
+            "jpg".to_string(),
+            "cr3".to_string(),
+            "xmp".to_string(),
+        ]);
+        args.exclude = vec!["cache".parse().unwrap(), "*.tmp.*".parse().unwrap()];
+        assert_eq!(
+            discover(root.path(), &args),
+            vec![
+                vec![PathBuf::from("a.JPG"), PathBuf::from("exported")],
+                vec![PathBuf::from("2023/IMG_0.jpg")],
+                vec![
+                    PathBuf::from("2024-06 Rome/IMG_1.CR3"),
+                    PathBuf::from("2024-06 Rome/IMG_1.xmp")
+                ],
+            ]
+        );
+
+        args.exclude_extensions = vec!["xmp".to_string()];
+        args.include = vec!["2024-*/**".parse().unwrap()];
+        assert_eq!(
+            discover(root.path(), &args),
+            vec![vec![PathBuf::from("2024-06 Rome/IMG_1.CR3")]]
+        );
+    }
+
+    #[test]
+    fn test_ignore_files() {
+        let root = tempfile::tempdir().unwrap();
+        for dir in ["edits/old", "trip/edits", "trip/day1", "other"] {
+            fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        fs::write(
+            root.path().join(IGNORE_FILE_NAME),
+            "# editor output\nedits/\n*_export.jpg\n",
+        )
+        .unwrap();
+        fs::write(
+            root.path().join("trip").join(IGNORE_FILE_NAME),
+            "!keep_export.jpg\n/day1\n",
+        )
+        .unwrap();
+        for name in [
+            "a.jpg",
+            "a_export.jpg",
+            "edits/old/b.jpg",
+            "trip/c.jpg",
+            "trip/c_export.jpg",
+            "trip/keep_export.jpg",
+            "trip/edits/d.jpg",
+            "trip/day1/e.jpg",
+            "other/f_export.jpg",
+            "other/g.jpg",
+        ] {
+            fs::write(root.path().join(name), "").unwrap();
+        }
+
+        let args = create_test_args(root.path());
+        assert_eq!(
+            discover(root.path(), &args),
+            vec![
+                vec![PathBuf::from("a.jpg")],
+                vec![PathBuf::from("other/g.jpg")],
+                vec![
+                    PathBuf::from("trip/c.jpg"),
+                    PathBuf::from("trip/keep_export.jpg")
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_system_directories() {
+        let root = tempfile::tempdir().unwrap();
+        for dir in [
+            "@eaDir/IMG_1.JPG",
+            "trip/@eaDir",
+            "$Recycle.Bin",
+            "trip/.Trashes",
+        ] {
+            fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        for name in [
+            "IMG_1.JPG",
+            "@eaDir/IMG_1.JPG/SYNOPHOTO_THUMB_XL.jpg",
+            "trip/IMG_2.JPG",
+            "trip/@eaDir/thumb.jpg",
+            "$Recycle.Bin/deleted.jpg",
+            "trip/.Trashes/deleted.jpg",
+        ] {
+            fs::write(root.path().join(name), "").unwrap();
+        }
+
+        let mut args = create_test_args(root.path());
+        assert_eq!(
+            discover(root.path(), &args),
+            vec![
+                vec![PathBuf::from("IMG_1.JPG")],
+                vec![PathBuf::from("trip/IMG_2.JPG")],
+            ]
+        );
+
+        args.include_system_dirs = true;
+        assert_eq!(discover(root.path(), &args).concat().len(), 6);
+
+        // The source itself may be one of them
+        let args = create_test_args(&root.path().join("@eaDir"));
+        assert_eq!(discover(root.path(), &args).concat().len(), 1);
+    }
+
+    #[test]
+    fn test_hidden_and_depth() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("a/b")).unwrap();
+        fs::create_dir_all(root.path().join(".hidden")).unwrap();
+        for name in [
+            "top.jpg",
+            ".top.jpg",
+            "a/one.jpg",
+            "a/b/two.jpg",
+            ".hidden/x.jpg",
+        ] {
+            fs::write(root.path().join(name), "").unwrap();
+        }
+
+        let mut args = create_test_args(root.path());
+        assert_eq!(discover(root.path(), &args).concat().len(), 5);
+
+        args.skip_hidden = true;
+        args.max_depth = Some(2);
+        assert_eq!(
+            discover(root.path(), &args),
+            vec![
+                vec![PathBuf::from("top.jpg")],
+                vec![PathBuf::from("a/one.jpg")]
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("a")).unwrap();
+        fs::write(root.path().join("a/one.jpg"), "").unwrap();
+        fs::write(root.path().join("b.jpg"), "").unwrap();
+        fs::write(root.path().join("d.jpg"), "").unwrap();
+        fs::write(outside.path().join("linked.jpg"), "").unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.path().join("card")).unwrap();
+        // Points back up the tree
+        std::os::unix::fs::symlink(root.path(), root.path().join("a/loop")).unwrap();
+
+        let mut args = create_test_args(root.path());
+        assert_eq!(
+            discover(root.path(), &args),
+            vec![
+                vec![PathBuf::from("b.jpg"), PathBuf::from("d.jpg")],
+                vec![PathBuf::from("a/one.jpg")]
+            ]
+        );
+
+        // The linked folder sorts among the folders, and the loop is skipped
+        args.follow_symlinks = true;
+        assert_eq!(
+            discover(root.path(), &args),
+            vec![
+                vec![PathBuf::from("b.jpg"), PathBuf::from("d.jpg")],
+                vec![PathBuf::from("a/one.jpg")],
+                vec![PathBuf::from("card/linked.jpg")]
+            ]
+        );
+    }
 }
@@ -0,0 +1,16 @@
+use crate::types::{FileTime, PhotoDateTime};
+use chrono::{DateTime, Datelike, Local};
+use std::fs;
+use std::path::Path;
+
+pub fn datetime_from_file_time(path: &Path, file_time: FileTime) -> Option<PhotoDateTime> {
+    let metadata = fs::metadata(path).ok()?;
+    let time = match file_time {
+        FileTime::Modified => metadata.modified(),
+        FileTime::Created => metadata.created().or_else(|_| metadata.modified()),
+    }
+    .ok()?;
+
+    let local: DateTime<Local> = time.into();
+    (local.year() >= 1970).then(|| local.fixed_offset().into())
+}
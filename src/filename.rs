@@ -0,0 +1,142 @@
+use crate::metadata::is_valid_datetime;
+use crate::types::{FilenamePattern, PhotoDateTime};
+use exif::DateTime;
+use regex::Captures;
+use std::path::Path;
+
+pub fn datetime_from_filename(path: &Path, patterns: &[FilenamePattern]) -> Option<PhotoDateTime> {
+    let name = path.file_name()?.to_str()?;
+
+    patterns
+        .iter()
+        .filter_map(|pattern| pattern.regex().captures(name))
+        .find_map(|captures| datetime_from_captures(&captures))
+}
+
+fn datetime_from_captures(captures: &Captures) -> Option<PhotoDateTime> {
+    let number = |group: &str| -> Option<u32> {
+        match captures.name(group) {
+            Some(m) => m.as_str().parse().ok(),
+            None => Some(0),
+        }
+    };
+
+    // A custom pattern may capture more than three digits, which are not milliseconds
+    let nanosecond = match captures.name("millis") {
+        Some(m) => Some(
+            m.as_str()
+                .parse::<u32>()
+                .ok()
+                .filter(|&millis| millis < 1000)?
+                * 1_000_000,
+        ),
+        None => None,
+    };
+
+    let dt = DateTime {
+        year: u16::try_from(number("year")?).ok()?,
+        month: u8::try_from(number("month")?).ok()?,
+        day: u8::try_from(number("day")?).ok()?,
+        hour: u8::try_from(number("hour")?).ok()?,
+        minute: u8::try_from(number("minute")?).ok()?,
+        second: u8::try_from(number("second")?).ok()?,
+        nanosecond,
+        offset: None,
+    };
+
+    is_valid_datetime(&dt).then(|| dt.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str) -> Option<String> {
+        datetime_from_filename(Path::new(name), &FilenamePattern::defaults()).map(|dt| {
+            let mut formatted = format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second
+            );
+            if let Some(nanosecond) = dt.nanosecond {
+                formatted += &format!(".{:03}", nanosecond / 1_000_000);
+            }
+            formatted
+        })
+    }
+
+    #[test]
+    fn test_camera_style() {
+        assert_eq!(
+            parse("IMG_20250615_143052.jpg"),
+            Some("2025-06-15 14:30:52".to_string())
+        );
+    }
+
+    #[test]
+    fn test_pixel_with_millis() {
+        assert_eq!(
+            parse("PXL_20250615_143052123.jpg"),
+            Some("2025-06-15 14:30:52.123".to_string())
+        );
+    }
+
+    #[test]
+    fn test_screenshot() {
+        assert_eq!(
+            parse("Screenshot_2025-06-15-14-30-52.png"),
+            Some("2025-06-15 14:30:52".to_string())
+        );
+    }
+
+    #[test]
+    fn test_whatsapp_date_only() {
+        assert_eq!(
+            parse("IMG-20250615-WA0003.jpg"),
+            Some("2025-06-15 00:00:00".to_string())
+        );
+    }
+
+    #[test]
+    fn test_nested_path_uses_file_name() {
+        assert_eq!(
+            parse("2024/IMG_20250615_143052.jpg"),
+            Some("2025-06-15 14:30:52".to_string())
+        );
+    }
+
+    #[test]
+    fn test_invalid_date() {
+        assert_eq!(parse("IMG_20251315_143052.jpg"), None);
+    }
+
+    #[test]
+    fn test_unrecognized_name() {
+        assert_eq!(parse("DSC_0001.jpg"), None);
+        assert_eq!(parse("IMG_2025061514305.jpg"), None);
+    }
+
+    #[test]
+    fn test_custom_pattern() {
+        let patterns = vec![
+            r"^scan_(?P<day>\d{2})\.(?P<month>\d{2})\.(?P<year>\d{4})"
+                .parse()
+                .unwrap(),
+        ];
+        let dt = datetime_from_filename(Path::new("scan_15.06.1999.tif"), &patterns).unwrap();
+        assert_eq!((dt.year, dt.month, dt.day, dt.hour), (1999, 6, 15, 0));
+    }
+
+    #[test]
+    fn test_custom_pattern_with_long_millis() {
+        let patterns = vec![
+            r"^cap_(?P<year>\d{4})(?P<month>\d{2})(?P<day>\d{2})_(?P<millis>\d+)"
+                .parse()
+                .unwrap(),
+        ];
+        let dt = datetime_from_filename(Path::new("cap_20250615_042.jpg"), &patterns).unwrap();
+        assert_eq!(dt.nanosecond, Some(42_000_000));
+        assert!(
+            datetime_from_filename(Path::new("cap_20250615_99999999.jpg"), &patterns).is_none()
+        );
+    }
+}
@@ -1,8 +1,17 @@
 pub mod processor;
 pub mod types;
 
+mod clock;
 mod discovery;
+mod file_time;
+mod filename;
 mod metadata;
 mod organizer;
+mod pairing;
+mod quicktime;
+mod raw;
+mod report;
 mod setup;
+mod takeout;
 mod transfer;
+mod xmp;
@@ -1,13 +1,14 @@
 use chronophoto::processor::process;
-use chronophoto::types::Args;
+use chronophoto::types::{Args, ClockCorrection, FilenamePattern, parse_date};
 use clap::Parser;
 use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct CliArgs {
-    /// Directory containing photos to organize
-    source: PathBuf,
+    /// Directories containing photos to organize, read as if they were one folder
+    #[arg(required = true)]
+    sources: Vec<PathBuf>,
 
     /// Root folder of the photo library
     library: PathBuf,
@@ -24,6 +25,10 @@ struct CliArgs {
     #[arg(short, long, default_value_t = false)]
     rename: bool,
 
+    /// Timestamp precision for renamed files: seconds or milliseconds
+    #[arg(long, default_value = "seconds")]
+    rename_format: String,
+
     /// File operation: move or copy
     #[arg(short, long, default_value = "move")]
     action: String,
@@ -39,26 +44,191 @@ struct CliArgs {
     /// Enable verbose logging
     #[arg(short, long, default_value_t = false)]
     verbose: bool,
+
+    /// EXIF date tags to try in priority order: original, digitized, modified
+    #[arg(
+        short = 't',
+        long,
+        value_delimiter = ',',
+        default_value = "original,digitized,modified"
+    )]
+    date_tags: Vec<String>,
+
+    /// Time zone for folders and names: local (camera time), utc or an offset like +02:00
+    #[arg(short = 'z', long, default_value = "local", allow_hyphen_values = true)]
+    timezone: String,
+
+    /// When to read dates from XMP sidecars and embedded XMP: ignore, fallback or prefer
+    #[arg(long, default_value = "fallback")]
+    xmp: String,
+
+    /// Date files without EXIF from common phone and messenger file names
+    #[arg(long, default_value_t = false)]
+    filename_dates: bool,
+
+    /// Custom file name regex with year, month, day (and optional hour, minute, second, millis) groups
+    #[arg(long)]
+    filename_pattern: Vec<String>,
+
+    /// Date remaining files from their file time (modified or created) into the _mtime subtree
+    #[arg(long)]
+    file_time: Option<String>,
+
+    /// File of per-camera clock corrections: make,model,serial,offset[,from,until] per line
+    #[arg(long)]
+    clock_offsets: Option<PathBuf>,
+
+    /// Threads reading metadata (0 = one per CPU core)
+    #[arg(short = 'j', long, default_value_t = 0)]
+    threads: usize,
+
+    /// Write skipped files and the reasons to this file: JSON if it ends in .json, CSV otherwise
+    #[arg(long)]
+    skip_report: Option<PathBuf>,
+
+    /// Move or copy files that cannot be dated into _undated, keeping their source folders
+    #[arg(long, default_value_t = false)]
+    quarantine: bool,
+
+    /// Only organize photos taken on or after this date (YYYY-MM-DD)
+    #[arg(long)]
+    since: Option<String>,
+
+    /// Only organize photos taken on or before this date (YYYY-MM-DD)
+    #[arg(long)]
+    until: Option<String>,
+
+    /// Extensions to read instead of the common photo, video and sidecar ones; * reads all files
+    #[arg(long, value_delimiter = ',')]
+    extensions: Option<Vec<String>>,
+
+    /// Extensions never to read
+    #[arg(long, value_delimiter = ',')]
+    exclude_extensions: Vec<String>,
+
+    /// Only read files matching this glob, relative to the source (repeatable)
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// Leave out files and folders matching this glob, relative to the source (repeatable)
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Also read NAS and OS metadata folders such as @eaDir, .thumbnails and $RECYCLE.BIN
+    #[arg(long, default_value_t = false)]
+    include_system_dirs: bool,
+
+    /// Follow symlinks to folders and files in the source
+    #[arg(long, default_value_t = false)]
+    follow_symlinks: bool,
+
+    /// Leave out files and folders whose name starts with a dot
+    #[arg(long, default_value_t = false)]
+    skip_hidden: bool,
+
+    /// How many folder levels below the source to read (1 = only the source itself)
+    #[arg(long)]
+    max_depth: Option<usize>,
 }
 
 impl TryFrom<CliArgs> for Args {
     type Error = String;
 
     fn try_from(cli: CliArgs) -> Result<Self, Self::Error> {
+        let mut filename_patterns = if cli.filename_dates {
+            FilenamePattern::defaults()
+        } else {
+            vec![]
+        };
+        for pattern in cli.filename_pattern {
+            filename_patterns.push(pattern.parse()?);
+        }
+
+        let clock_corrections = match cli.clock_offsets {
+            Some(path) => {
+                let table = std::fs::read_to_string(&path).map_err(|e| {
+                    format!("Error: cannot read clock offsets {}: {}", path.display(), e)
+                })?;
+                ClockCorrection::parse_table(&table)?
+            }
+            None => vec![],
+        };
+
+        let since = cli.since.as_deref().map(parse_date).transpose()?;
+        let until = cli.until.as_deref().map(parse_date).transpose()?;
+        if let (Some(since), Some(until)) = (since, until)
+            && since > until
+        {
+            return Err(format!(
+                "Error: date range {} to {} ends before it starts",
+                since, until
+            ));
+        }
+
+        if cli.max_depth == Some(0) {
+            return Err("Error: --max-depth must be at least 1, the source itself".to_string());
+        }
+
+        let extensions = match cli.extensions {
+            Some(extensions) if extensions.iter().any(|ext| ext == "*") => Some(vec![]),
+            extensions => extensions.map(normalize_extensions),
+        };
+
         Ok(Args {
-            source: cli.source,
+            sources: cli.sources,
             mode: cli.mode.parse()?,
             action: cli.action.parse()?,
             library: cli.library,
             limit: cli.limit,
             rename: cli.rename,
+            rename_format: cli.rename_format.parse()?,
             log_file: cli.log_file,
             dry_run: cli.dry_run,
             verbose: cli.verbose,
+            date_tags: cli
+                .date_tags
+                .iter()
+                .map(|tag| tag.parse())
+                .collect::<Result<_, _>>()?,
+            timezone: cli.timezone.parse()?,
+            xmp: cli.xmp.parse()?,
+            filename_patterns,
+            file_time: cli.file_time.map(|t| t.parse()).transpose()?,
+            clock_corrections,
+            threads: cli.threads,
+            skip_report: cli.skip_report,
+            quarantine: cli.quarantine,
+            since,
+            until,
+            extensions,
+            exclude_extensions: normalize_extensions(cli.exclude_extensions),
+            include: cli
+                .include
+                .iter()
+                .map(|pattern| pattern.parse())
+                .collect::<Result<_, _>>()?,
+            exclude: cli
+                .exclude
+                .iter()
+                .map(|pattern| pattern.parse())
+                .collect::<Result<_, _>>()?,
+            include_system_dirs: cli.include_system_dirs,
+            follow_symlinks: cli.follow_symlinks,
+            skip_hidden: cli.skip_hidden,
+            max_depth: cli.max_depth,
         })
     }
 }
 
+/// Accepts `.JPG` as well as `jpg`
+fn normalize_extensions(extensions: Vec<String>) -> Vec<String> {
+    extensions
+        .iter()
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = CliArgs::parse();
     let has_log_file = args.log_file.is_some();
@@ -76,3 +246,25 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(extra: &[&str]) -> Result<Args, String> {
+        let cli = CliArgs::try_parse_from(["chronophoto", "source", "library"].iter().chain(extra))
+            .map_err(|e| e.to_string())?;
+        cli.try_into()
+    }
+
+    #[test]
+    fn test_max_depth() {
+        assert_eq!(parse(&["--max-depth", "1"]).unwrap().max_depth, Some(1));
+        assert!(
+            parse(&["--max-depth", "0"])
+                .err()
+                .unwrap()
+                .starts_with("Error: --max-depth")
+        );
+    }
+}
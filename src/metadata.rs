@@ -1,9 +1,28 @@
-use crate::types::{PhotoDateTime, PhotoMetadata};
-use chrono::NaiveDate;
-use exif::{DateTime, In, Reader, Tag, Value};
-use log::warn;
+use crate::clock::{Camera, correct_clock};
+use crate::file_time::datetime_from_file_time;
+use crate::filename::datetime_from_filename;
+use crate::pairing::is_sidecar;
+use crate::quicktime::{extract_video_datetime, is_video};
+use crate::raw::read_exif;
+use crate::takeout::extract_takeout_datetime;
+use crate::types::{
+    Args, DateSource, DateTag, PhotoDateTime, PhotoMetadata, SkipReason, SkippedFile, TimeZone,
+    XmpMode,
+};
+use crate::xmp::extract_xmp_datetime;
+use chrono::{Datelike, FixedOffset, NaiveDate, TimeDelta, Timelike};
+use exif::{DateTime, Exif, Field, In, Tag, Value};
+use log::{debug, warn};
+use rayon::iter::Either;
+use rayon::prelude::*;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 
+const APPLE_MAKER_NOTE_HEADER: &[u8] = b"Apple iOS\0";
+
+/// Apple maker note tag linking a Live Photo still to its video
+const APPLE_CONTENT_IDENTIFIER: u16 = 0x0011;
+
 impl From<DateTime> for PhotoDateTime {
     fn from(dt: DateTime) -> Self {
         Self {
@@ -13,6 +32,73 @@ impl From<DateTime> for PhotoDateTime {
             hour: dt.hour,
             minute: dt.minute,
             second: dt.second,
+            offset: dt.offset,
+            nanosecond: dt.nanosecond,
+        }
+    }
+}
+
+impl From<chrono::DateTime<FixedOffset>> for PhotoDateTime {
+    fn from(dt: chrono::DateTime<FixedOffset>) -> Self {
+        Self {
+            year: dt.year() as u16,
+            month: dt.month() as u8,
+            day: dt.day() as u8,
+            hour: dt.hour() as u8,
+            minute: dt.minute() as u8,
+            second: dt.second() as u8,
+            offset: Some((dt.offset().local_minus_utc() / 60) as i16),
+            nanosecond: None,
+        }
+    }
+}
+
+impl PhotoDateTime {
+    /// Calendar date of the capture
+    pub(crate) fn date(&self) -> NaiveDate {
+        NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)
+            .expect("photo datetime should be validated on extraction")
+    }
+
+    /// Moves the wall-clock time, rolling over days, months and years
+    pub(crate) fn shifted(self, delta: TimeDelta) -> Self {
+        let naive = self
+            .date()
+            .and_hms_opt(self.hour as u32, self.minute as u32, self.second as u32)
+            .expect("photo datetime should be validated on extraction")
+            + delta;
+
+        Self {
+            year: naive.year() as u16,
+            month: naive.month() as u8,
+            day: naive.day() as u8,
+            hour: naive.hour() as u8,
+            minute: naive.minute() as u8,
+            second: naive.second() as u8,
+            ..self
+        }
+    }
+
+    /// Key ordering photos by capture time, down to sub-second precision
+    fn sort_key(&self) -> (u16, u8, u8, u8, u8, u8, u32) {
+        (
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            self.nanosecond.unwrap_or(0),
+        )
+    }
+}
+
+impl From<DateTag> for Tag {
+    fn from(date_tag: DateTag) -> Self {
+        match date_tag {
+            DateTag::Original => Tag::DateTimeOriginal,
+            DateTag::Digitized => Tag::DateTimeDigitized,
+            DateTag::Modified => Tag::DateTime,
         }
     }
 }
@@ -23,6 +109,23 @@ pub enum ExifError {
     ReadData(exif::Error),
     ParseDateError(exif::Error),
     NoDataError,
+    NoVideoDateError,
+    SidecarError,
+}
+
+impl ExifError {
+    fn skip_reason(&self) -> SkipReason {
+        match self {
+            // Cut-off files fail while reading the header
+            Self::Io(err) if err.kind() == ErrorKind::UnexpectedEof => SkipReason::Unreadable,
+            Self::Io(_) | Self::ReadData(exif::Error::Io(_)) => SkipReason::Io,
+            Self::ReadData(exif::Error::NotFound(_)) => SkipReason::NoDate,
+            Self::ReadData(_) => SkipReason::Unreadable,
+            Self::ParseDateError(_) => SkipReason::InvalidDate,
+            Self::NoDataError | Self::NoVideoDateError => SkipReason::NoDate,
+            Self::SidecarError => SkipReason::OrphanSidecar,
+        }
+    }
 }
 
 impl From<std::io::Error> for ExifError {
@@ -44,38 +147,227 @@ impl std::fmt::Display for ExifError {
             Self::ReadData(exif_error) => write!(f, "{}", exif_error),
             Self::ParseDateError(exif_error) => write!(f, "{}", exif_error),
             Self::NoDataError => write!(f, "No DateTime field in EXIF data"),
+            Self::NoVideoDateError => write!(f, "No creation time in QuickTime metadata"),
+            Self::SidecarError => write!(f, "Sidecar file without its photo"),
         }
     }
 }
 
-pub fn paths_to_metadata(paths: Vec<PathBuf>) -> Vec<PhotoMetadata> {
-    paths
-        .into_iter()
-        .map(|path| (path.clone(), extract_datetime(&path)))
-        .filter_map(|(path, result)| match result {
-            Ok(value) => Some((path, value)),
+/// Dates every group in parallel on the current rayon thread pool, returning the dated
+/// photos and every file of the groups that could not be dated.
+/// The result does not depend on scheduling: it is sorted by capture time, ties in input order.
+pub fn paths_to_metadata(
+    groups: Vec<Vec<PathBuf>>,
+    args: &Args,
+) -> (Vec<PhotoMetadata>, Vec<SkippedFile>) {
+    let (mut metadata, skipped): (Vec<PhotoMetadata>, Vec<Vec<SkippedFile>>) = groups
+        .into_par_iter()
+        .map(|group| {
+            let result = date_group(&group, args);
+            (group, result)
+        })
+        .partition_map(|(group, result)| match result {
+            Ok((dt, source, camera)) => {
+                let dt = apply_clock_corrections(dt, source, &camera, args);
+                let mut members = group.into_iter();
+                Either::Left(PhotoMetadata {
+                    path: members.next().expect("groups should not be empty"),
+                    datetime: to_timezone(dt, args.timezone),
+                    source,
+                    companions: members.collect(),
+                })
+            }
             Err(e) => {
                 warn!(
                     "Failed to extract EXIF metadata from {}: {}",
-                    path.display(),
+                    group[0].display(),
                     e
                 );
-                None
+                let reason = e.skip_reason();
+                Either::Right(
+                    group
+                        .into_iter()
+                        .map(|path| SkippedFile {
+                            path,
+                            reason,
+                            detail: e.to_string(),
+                        })
+                        .collect(),
+                )
             }
-        })
-        .map(|(path, dt)| PhotoMetadata { path, datetime: dt })
-        .collect()
+        });
+
+    // Chronological order makes name conflicts within a burst numbered in shooting order
+    metadata.sort_by_key(|md| md.datetime.sort_key());
+    (metadata, skipped.into_iter().flatten().collect())
+}
+
+/// Fixes the camera clock before the date is used for folders and names.
+/// File times do not come from the camera, so they are left alone.
+fn apply_clock_corrections(
+    dt: PhotoDateTime,
+    source: DateSource,
+    camera: &Camera,
+    args: &Args,
+) -> PhotoDateTime {
+    if args.clock_corrections.is_empty() || source == DateSource::FileTime {
+        return dt;
+    }
+    correct_clock(dt, camera, &args.clock_corrections)
+}
+
+/// Dates a group of files from whichever member carries a date, preferring
+/// embedded metadata of any member over file name and file time fallbacks.
+/// The camera comes from the first member whose EXIF could be read.
+fn date_group(
+    group: &[PathBuf],
+    args: &Args,
+) -> Result<(PhotoDateTime, DateSource, Camera), ExifError> {
+    let mut fallback = None;
+    let mut first_error = None;
+    let mut camera = None;
+
+    for path in group {
+        match date_photo(path, args, &mut camera) {
+            Ok((dt, source)) if !matches!(source, DateSource::Filename | DateSource::FileTime) => {
+                return Ok((dt, source, camera.unwrap_or_default()));
+            }
+            Ok(dated) => {
+                fallback.get_or_insert(dated);
+            }
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    match (fallback, first_error) {
+        (Some((dt, source)), _) => Ok((dt, source, camera.unwrap_or_default())),
+        (None, Some(e)) => Err(e),
+        (None, None) => Err(ExifError::NoDataError),
+    }
+}
+
+/// Dates one file, recording the camera in `camera` when none is known yet. Its EXIF is
+/// read once, both for the date and for the camera.
+fn date_photo(
+    path: &Path,
+    args: &Args,
+    camera: &mut Option<Camera>,
+) -> Result<(PhotoDateTime, DateSource), ExifError> {
+    if is_sidecar(path) {
+        return Err(ExifError::SidecarError);
+    }
+
+    let exif = (!is_video(path)).then(|| read_exif(path));
+    if camera.is_none()
+        && let Some(Ok(exif)) = &exif
+    {
+        *camera = Some(Camera {
+            make: read_ascii(exif, Tag::Make),
+            model: read_ascii(exif, Tag::Model),
+            serial: read_ascii(exif, Tag::BodySerialNumber),
+        });
+    }
+
+    if args.xmp == XmpMode::Prefer
+        && let Some(dt) = extract_xmp_datetime(path)
+    {
+        return Ok((dt, DateSource::Xmp));
+    }
+
+    let error = match extract_embedded_datetime(path, exif, args) {
+        Ok(dated) => return Ok(dated),
+        Err(e) => e,
+    };
+
+    if args.xmp == XmpMode::Fallback
+        && let Some(dt) = extract_xmp_datetime(path)
+    {
+        debug!(
+            "No embedded date in {} ({}), dated from XMP",
+            path.display(),
+            error
+        );
+        return Ok((dt, DateSource::Xmp));
+    }
+
+    if let Some(dt) = extract_takeout_datetime(path) {
+        debug!(
+            "No embedded date in {} ({}), dated from its Takeout JSON",
+            path.display(),
+            error
+        );
+        return Ok((dt, DateSource::Takeout));
+    }
+
+    if let Some(dt) = datetime_from_filename(path, &args.filename_patterns) {
+        debug!(
+            "No embedded date in {} ({}), dated from its file name",
+            path.display(),
+            error
+        );
+        return Ok((dt, DateSource::Filename));
+    }
+
+    if let Some(dt) = args
+        .file_time
+        .and_then(|file_time| datetime_from_file_time(path, file_time))
+    {
+        warn!(
+            "No embedded date in {} ({}), dated from its file time",
+            path.display(),
+            error
+        );
+        return Ok((dt, DateSource::FileTime));
+    }
+
+    Err(error)
+}
+
+/// Dates a video from its QuickTime metadata, or any other file from its EXIF read by the
+/// caller (`None` for videos)
+fn extract_embedded_datetime(
+    path: &Path,
+    exif: Option<Result<Vec<Exif>, ExifError>>,
+    args: &Args,
+) -> Result<(PhotoDateTime, DateSource), ExifError> {
+    match exif {
+        None => extract_video_datetime(path).map(|dt| (dt, DateSource::Video)),
+        Some(exif) => extract_datetime(&exif?, &args.date_tags).map(|dt| (dt, DateSource::Exif)),
+    }
 }
 
-fn extract_datetime(path: &Path) -> Result<PhotoDateTime, ExifError> {
-    let file = std::fs::File::open(path)?;
-    let mut bufreader = std::io::BufReader::new(&file);
-    let exifreader = Reader::new();
-    let exif = exifreader.read_from_container(&mut bufreader)?;
-    if let Some(field) = exif.get_field(Tag::DateTime, In::PRIMARY) {
+fn extract_datetime(exif: &[Exif], date_tags: &[DateTag]) -> Result<PhotoDateTime, ExifError> {
+    // The first readable tag wins; otherwise report the first tag that was present but unusable
+    let mut error = ExifError::NoDataError;
+    for date_tag in date_tags {
+        match read_datetime(exif, *date_tag) {
+            Ok(dt) => return Ok(dt),
+            Err(ExifError::NoDataError) => (),
+            Err(err) if matches!(error, ExifError::NoDataError) => error = err,
+            Err(_) => (),
+        }
+    }
+    Err(error)
+}
+
+/// Looks a tag up by number, as CR3 stores Exif IFD tags in a TIFF stream of their own
+fn find_field(exif: &[Exif], tag: Tag) -> Option<&Field> {
+    exif.iter()
+        .flat_map(|exif| exif.fields())
+        .find(|field| field.ifd_num == In::PRIMARY && field.tag.number() == tag.number())
+}
+
+fn read_datetime(exif: &[Exif], date_tag: DateTag) -> Result<PhotoDateTime, ExifError> {
+    if let Some(field) = find_field(exif, date_tag.into()) {
         match field.value {
             Value::Ascii(ref vec) if !vec.is_empty() => match DateTime::from_ascii(&vec[0]) {
-                Ok(dt) if is_valid_datetime(&dt) => return Ok(dt.into()),
+                Ok(mut dt) if is_valid_datetime(&dt) => {
+                    read_offset(exif, date_tag, &mut dt);
+                    read_subsec(exif, date_tag, &mut dt);
+                    return Ok(dt.into());
+                }
                 Err(err) => return Err(ExifError::ParseDateError(err)),
                 _ => return Err(ExifError::NoDataError),
             },
@@ -85,7 +377,126 @@ fn extract_datetime(path: &Path) -> Result<PhotoDateTime, ExifError> {
     Err(ExifError::NoDataError)
 }
 
-fn is_valid_datetime(dt: &DateTime) -> bool {
+fn read_ascii(exif: &[Exif], tag: Tag) -> Option<String> {
+    match find_field(exif, tag)?.value {
+        Value::Ascii(ref vec) if !vec.is_empty() => {
+            let value = String::from_utf8_lossy(&vec[0])
+                .trim_end_matches('\0')
+                .trim()
+                .to_string();
+            (!value.is_empty()).then_some(value)
+        }
+        _ => None,
+    }
+}
+
+fn read_offset(exif: &[Exif], date_tag: DateTag, dt: &mut DateTime) {
+    let tag = match date_tag {
+        DateTag::Original => Tag::OffsetTimeOriginal,
+        DateTag::Digitized => Tag::OffsetTimeDigitized,
+        DateTag::Modified => Tag::OffsetTime,
+    };
+
+    if let Some(field) = find_field(exif, tag) {
+        match field.value {
+            Value::Ascii(ref vec) if !vec.is_empty() => {
+                // A malformed offset leaves the time as camera local time
+                let _ = dt.parse_offset(&vec[0]);
+            }
+            _ => (),
+        }
+    }
+}
+
+fn read_subsec(exif: &[Exif], date_tag: DateTag, dt: &mut DateTime) {
+    let tag = match date_tag {
+        DateTag::Original => Tag::SubSecTimeOriginal,
+        DateTag::Digitized => Tag::SubSecTimeDigitized,
+        DateTag::Modified => Tag::SubSecTime,
+    };
+
+    if let Some(field) = find_field(exif, tag) {
+        match field.value {
+            Value::Ascii(ref vec) if !vec.is_empty() => {
+                // A malformed value leaves the time with whole seconds only
+                let _ = dt.parse_subsec(&vec[0]);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Reads the Live Photo content identifier from the Apple maker note of a still
+pub(crate) fn extract_content_identifier(path: &Path) -> Option<String> {
+    let exif = read_exif(path).ok()?;
+    match find_field(&exif, Tag::MakerNote)?.value {
+        Value::Undefined(ref note, _) => apple_maker_note_string(note, APPLE_CONTENT_IDENTIFIER),
+        _ => None,
+    }
+}
+
+/// Apple maker notes are `Apple iOS\0`, a version, a byte order mark and an IFD whose
+/// offsets are relative to the start of the note
+fn apple_maker_note_string(note: &[u8], tag: u16) -> Option<String> {
+    if !note.starts_with(APPLE_MAKER_NOTE_HEADER) {
+        return None;
+    }
+    let big_endian = match note.get(12..14)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| -> Option<u16> {
+        let bytes = note.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let u32_at = |offset: usize| -> Option<usize> {
+        let bytes = note.get(offset..offset + 4)?.try_into().ok()?;
+        let value = if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        };
+        usize::try_from(value).ok()
+    };
+
+    let entry = (0..usize::from(u16_at(14)?))
+        .map(|index| 16 + index * 12)
+        .find(|&entry| u16_at(entry) == Some(tag))?;
+
+    // Only ASCII values are meaningful here
+    if u16_at(entry + 2)? != 2 {
+        return None;
+    }
+    let len = u32_at(entry + 4)?;
+    let value = if len <= 4 {
+        note.get(entry + 8..entry + 8 + len)?
+    } else {
+        let offset = u32_at(entry + 8)?;
+        note.get(offset..offset.checked_add(len)?)?
+    };
+
+    let value = String::from_utf8_lossy(value)
+        .trim_end_matches('\0')
+        .to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+fn to_timezone(dt: PhotoDateTime, timezone: TimeZone) -> PhotoDateTime {
+    match (timezone, dt.offset) {
+        (TimeZone::Offset(target), Some(offset)) => PhotoDateTime {
+            offset: Some(target),
+            ..dt.shifted(TimeDelta::minutes(i64::from(target) - i64::from(offset)))
+        },
+        _ => dt,
+    }
+}
+
+pub(crate) fn is_valid_datetime(dt: &DateTime) -> bool {
     if dt.year < 1970 {
         return false;
     }
@@ -99,6 +510,127 @@ fn is_valid_datetime(dt: &DateTime) -> bool {
 mod tests {
     use super::*;
 
+    mod apple_maker_note_string {
+        use super::*;
+
+        const IDENTIFIER: &str = "6C1F4C3B-2B8E-4B8C-9C2A-4E0F1E7D9A10";
+
+        fn maker_note(entries: &[(u16, u16, &str)]) -> Vec<u8> {
+            let mut note = APPLE_MAKER_NOTE_HEADER.to_vec();
+            note.extend_from_slice(&[0, 1]);
+            note.extend_from_slice(b"MM");
+            note.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+
+            let mut data_offset = 16 + entries.len() * 12 + 4;
+            let mut data = Vec::new();
+            for (tag, kind, value) in entries {
+                let mut value = value.as_bytes().to_vec();
+                value.push(0);
+                note.extend_from_slice(&tag.to_be_bytes());
+                note.extend_from_slice(&kind.to_be_bytes());
+                note.extend_from_slice(&(value.len() as u32).to_be_bytes());
+                if value.len() <= 4 {
+                    value.resize(4, 0);
+                    note.extend_from_slice(&value);
+                } else {
+                    note.extend_from_slice(&(data_offset as u32).to_be_bytes());
+                    data_offset += value.len();
+                    data.extend(value);
+                }
+            }
+            note.extend_from_slice(&[0; 4]);
+            note.extend(data);
+            note
+        }
+
+        #[test]
+        fn test_content_identifier() {
+            let note = maker_note(&[(0x0008, 2, "abc"), (0x0011, 2, IDENTIFIER)]);
+            assert_eq!(
+                apple_maker_note_string(&note, APPLE_CONTENT_IDENTIFIER),
+                Some(IDENTIFIER.to_string())
+            );
+        }
+
+        #[test]
+        fn test_inline_value() {
+            let note = maker_note(&[(0x0011, 2, "ab")]);
+            assert_eq!(
+                apple_maker_note_string(&note, APPLE_CONTENT_IDENTIFIER),
+                Some("ab".to_string())
+            );
+        }
+
+        #[test]
+        fn test_missing_or_not_ascii() {
+            let note = maker_note(&[(0x0008, 2, IDENTIFIER), (0x0011, 7, IDENTIFIER)]);
+            assert!(apple_maker_note_string(&note, APPLE_CONTENT_IDENTIFIER).is_none());
+            assert!(apple_maker_note_string(&note, 0x0020).is_none());
+        }
+
+        #[test]
+        fn test_other_vendor() {
+            let mut note = maker_note(&[(0x0011, 2, IDENTIFIER)]);
+            note[..5].copy_from_slice(b"Nikon");
+            assert!(apple_maker_note_string(&note, APPLE_CONTENT_IDENTIFIER).is_none());
+        }
+
+        #[test]
+        fn test_truncated() {
+            let note = maker_note(&[(0x0011, 2, IDENTIFIER)]);
+            assert!(apple_maker_note_string(&note[..40], APPLE_CONTENT_IDENTIFIER).is_none());
+        }
+    }
+
+    mod to_timezone {
+        use super::*;
+
+        fn photo_datetime(offset: Option<i16>) -> PhotoDateTime {
+            PhotoDateTime {
+                year: 2025,
+                month: 12,
+                day: 31,
+                hour: 23,
+                minute: 30,
+                second: 15,
+                offset,
+                nanosecond: None,
+            }
+        }
+
+        fn ymd_hms(dt: PhotoDateTime) -> (u16, u8, u8, u8, u8, u8) {
+            (dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second)
+        }
+
+        #[test]
+        fn test_local_keeps_camera_time() {
+            let dt = to_timezone(photo_datetime(Some(120)), TimeZone::Local);
+            assert_eq!(ymd_hms(dt), (2025, 12, 31, 23, 30, 15));
+            assert_eq!(dt.offset, Some(120));
+        }
+
+        #[test]
+        fn test_target_without_camera_offset() {
+            let dt = to_timezone(photo_datetime(None), TimeZone::Offset(0));
+            assert_eq!(ymd_hms(dt), (2025, 12, 31, 23, 30, 15));
+            assert_eq!(dt.offset, None);
+        }
+
+        #[test]
+        fn test_convert_to_utc() {
+            let dt = to_timezone(photo_datetime(Some(120)), TimeZone::Offset(0));
+            assert_eq!(ymd_hms(dt), (2025, 12, 31, 21, 30, 15));
+            assert_eq!(dt.offset, Some(0));
+        }
+
+        #[test]
+        fn test_year_rollover() {
+            let dt = to_timezone(photo_datetime(Some(-300)), TimeZone::Offset(60));
+            assert_eq!(ymd_hms(dt), (2026, 1, 1, 5, 30, 15));
+            assert_eq!(dt.offset, Some(60));
+        }
+    }
+
     mod is_valid_datetime {
         use super::*;
 
@@ -1,54 +1,133 @@
-use crate::types::{Args, Mode, PhotoMetadata};
+use crate::setup::resolve;
+use crate::types::{Args, DateSource, Mode, PhotoMetadata, RenameFormat, SkippedFile};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-pub fn from_to_paths(metadata: Vec<PhotoMetadata>, args: &Args) -> Vec<(PathBuf, PathBuf)> {
+/// Source, destination and the companion files that follow the destination name
+pub type PathGroup = (PathBuf, PathBuf, Vec<PathBuf>);
+
+/// Photos per month folder across the whole run, which compact mode needs before placing any
+pub type MonthCounts = HashMap<String, usize>;
+
+/// Subtree for photos dated from filesystem timestamps, kept apart for manual review
+const FILE_TIME_FOLDER: &str = "_mtime";
+
+/// Subtree for files that could not be dated, mirroring their place in the source
+pub const UNDATED_FOLDER: &str = "_undated";
+
+pub fn from_to_paths(
+    metadata: Vec<PhotoMetadata>,
+    args: &Args,
+    month_counts: &MonthCounts,
+) -> Vec<PathGroup> {
+    let rename = rename_format(args);
     match args.mode {
         Mode::Daily => metadata
             .into_iter()
-            .map(|md| build_daily_path(&md, args.library.clone(), args.rename))
+            .map(|md| build_daily_path(&md, args.library.clone(), rename))
             .collect(),
         Mode::Monthly => metadata
             .into_iter()
-            .map(|md| build_monthly_path(&md, args.library.clone(), args.rename))
+            .map(|md| build_monthly_path(&md, args.library.clone(), rename))
             .collect(),
-        Mode::Compact => build_compact_paths(metadata, args),
+        Mode::Compact => build_compact_paths(metadata, args, month_counts),
         Mode::Flat => build_flat_paths(metadata, args),
     }
 }
 
-fn files_per_month(metadata: &[PhotoMetadata]) -> HashMap<String, u16> {
-    metadata.iter().fold(HashMap::new(), |mut acc, md| {
-        let key = group_key(md);
-        match acc.get(&key) {
-            Some(v) => acc.insert(key, v + 1),
-            None => acc.insert(key, 1),
-        };
-        acc
-    })
+/// Keeps photos taken within `--since` and `--until`, inclusive; also returns how many
+/// files were left out, companions included
+pub fn filter_date_range(metadata: Vec<PhotoMetadata>, args: &Args) -> (Vec<PhotoMetadata>, usize) {
+    if args.since.is_none() && args.until.is_none() {
+        return (metadata, 0);
+    }
+
+    let (kept, outside): (Vec<PhotoMetadata>, Vec<PhotoMetadata>) =
+        metadata.into_iter().partition(|md| {
+            let date = md.datetime.date();
+            args.since.is_none_or(|since| date >= since)
+                && args.until.is_none_or(|until| date <= until)
+        });
+    let files = outside.iter().map(|md| 1 + md.companions.len()).sum();
+    (kept, files)
+}
+
+/// Destinations under `_undated` for files that could not be dated, keeping their path
+/// relative to their source so related files stay together. Files already there stay put.
+pub fn quarantine_paths(skipped: &[SkippedFile], args: &Args) -> Vec<PathGroup> {
+    let undated = args.library.join(UNDATED_FOLDER);
+    // Compared resolved, as the source may name the library another way, e.g. `.`
+    let resolved_undated = resolve(&undated).ok();
+    let is_quarantined = |path: &Path| {
+        let resolved = path.parent().and_then(|parent| resolve(parent).ok());
+        match (resolved, &resolved_undated) {
+            (Some(folder), Some(undated)) => folder.starts_with(undated),
+            _ => path.starts_with(&undated),
+        }
+    };
+    skipped
+        .iter()
+        .map(|file| {
+            if is_quarantined(&file.path) {
+                return (file.path.clone(), file.path.clone(), vec![]);
+            }
+            let relative = args
+                .sources
+                .iter()
+                .find_map(|source| file.path.strip_prefix(source).ok())
+                .unwrap_or_else(|| Path::new(file.path.file_name().unwrap_or_default()));
+            (file.path.clone(), undated.join(relative), vec![])
+        })
+        .collect()
+}
+
+fn rename_format(args: &Args) -> Option<RenameFormat> {
+    args.rename.then_some(args.rename_format)
+}
+
+pub fn count_per_month(metadata: &[PhotoMetadata], counts: &mut MonthCounts) {
+    for md in metadata {
+        *counts.entry(group_key(md)).or_insert(0) += 1;
+    }
 }
 
 fn group_key(md: &PhotoMetadata) -> String {
-    format!("{}-{}", md.datetime.year, md.datetime.month)
+    if md.source == DateSource::FileTime {
+        format!(
+            "{}/{}-{}",
+            FILE_TIME_FOLDER, md.datetime.year, md.datetime.month
+        )
+    } else {
+        format!("{}-{}", md.datetime.year, md.datetime.month)
+    }
 }
 
-fn build_compact_paths(metadata: Vec<PhotoMetadata>, args: &Args) -> Vec<(PathBuf, PathBuf)> {
-    let stats = files_per_month(&metadata);
+fn build_compact_paths(
+    metadata: Vec<PhotoMetadata>,
+    args: &Args,
+    month_counts: &MonthCounts,
+) -> Vec<PathGroup> {
+    let rename = rename_format(args);
     metadata
         .into_iter()
         .map(|md| {
             let key = group_key(&md);
-            let quantity = *stats.get(&key).expect("Should be at least 1");
-            if quantity > args.limit {
-                build_daily_path(&md, args.library.clone(), args.rename)
+            // A photo missing from the counts is placed as if it were alone in its month
+            let quantity = month_counts.get(&key).copied().unwrap_or(1);
+            if quantity > usize::from(args.limit) {
+                build_daily_path(&md, args.library.clone(), rename)
             } else {
-                build_monthly_path(&md, args.library.clone(), args.rename)
+                build_monthly_path(&md, args.library.clone(), rename)
             }
         })
         .collect()
 }
 
-fn build_daily_path(md: &PhotoMetadata, library: PathBuf, rename: bool) -> (PathBuf, PathBuf) {
+fn build_daily_path(
+    md: &PhotoMetadata,
+    library: PathBuf,
+    rename: Option<RenameFormat>,
+) -> PathGroup {
     let folder = format!(
         "{:04}/{:02}/{:02}",
         md.datetime.year, md.datetime.month, md.datetime.day
@@ -56,24 +135,32 @@ fn build_daily_path(md: &PhotoMetadata, library: PathBuf, rename: bool) -> (Path
     (
         md.path.clone(),
         build_path(md, library, rename, Some(folder)),
+        md.companions.clone(),
     )
 }
 
-fn build_monthly_path(md: &PhotoMetadata, library: PathBuf, rename: bool) -> (PathBuf, PathBuf) {
+fn build_monthly_path(
+    md: &PhotoMetadata,
+    library: PathBuf,
+    rename: Option<RenameFormat>,
+) -> PathGroup {
     let folder = format!("{:04}/{:02}", md.datetime.year, md.datetime.month);
     (
         md.path.clone(),
         build_path(md, library, rename, Some(folder)),
+        md.companions.clone(),
     )
 }
 
-fn build_flat_paths(metadata: Vec<PhotoMetadata>, args: &Args) -> Vec<(PathBuf, PathBuf)> {
+fn build_flat_paths(metadata: Vec<PhotoMetadata>, args: &Args) -> Vec<PathGroup> {
+    let rename = rename_format(args);
     metadata
         .into_iter()
         .map(|md| {
             (
                 md.path.clone(),
-                build_path(&md, args.library.clone(), args.rename, None),
+                build_path(&md, args.library.clone(), rename, None),
+                md.companions.clone(),
             )
         })
         .collect()
@@ -82,18 +169,23 @@ fn build_flat_paths(metadata: Vec<PhotoMetadata>, args: &Args) -> Vec<(PathBuf,
 fn build_path(
     md: &PhotoMetadata,
     library: PathBuf,
-    rename: bool,
+    rename: Option<RenameFormat>,
     folder: Option<String>,
 ) -> PathBuf {
     let file_name = build_filename(md, rename).expect("photo must have filename");
+    let library = if md.source == DateSource::FileTime {
+        library.join(FILE_TIME_FOLDER)
+    } else {
+        library
+    };
     match folder {
         Some(folder) => library.join(folder).join(file_name),
         None => library.join(file_name),
     }
 }
 
-fn build_filename(md: &PhotoMetadata, rename: bool) -> Result<String, String> {
-    if rename {
+fn build_filename(md: &PhotoMetadata, rename: Option<RenameFormat>) -> Result<String, String> {
+    if let Some(format) = rename {
         let mut file_name = format!(
             "{:04}{:02}{:02}_{:02}{:02}{:02}",
             md.datetime.year,
@@ -103,6 +195,9 @@ fn build_filename(md: &PhotoMetadata, rename: bool) -> Result<String, String> {
             md.datetime.minute,
             md.datetime.second
         );
+        if format == RenameFormat::Milliseconds {
+            file_name += &format!("_{:03}", md.datetime.nanosecond.unwrap_or(0) / 1_000_000);
+        }
         if let Some(ext) = md.path.extension() {
             file_name += &format!(".{}", ext.display());
         };
@@ -118,22 +213,25 @@ fn build_filename(md: &PhotoMetadata, rename: bool) -> Result<String, String> {
 mod tests {
     use super::*;
 
-    use crate::types::{Action, PhotoDateTime};
+    use crate::types::{DateSource, PhotoDateTime};
+    use chrono::NaiveDate;
 
     fn create_test_args(mode: Mode, rename: bool, limit: u16) -> Args {
         Args {
-            source: PathBuf::from("source"),
-            library: PathBuf::from("test_dir"),
             mode,
             limit,
             rename,
-            action: Action::Move,
-            dry_run: false,
-            log_file: None,
-            verbose: false,
+            extensions: Some(vec![]),
+            ..Args::new(vec![PathBuf::from("source")], PathBuf::from("test_dir"))
         }
     }
 
+    fn paths_for(metadata: Vec<PhotoMetadata>, args: &Args) -> Vec<PathGroup> {
+        let mut month_counts = MonthCounts::new();
+        count_per_month(&metadata, &mut month_counts);
+        from_to_paths(metadata, args, &month_counts)
+    }
+
     fn create_test_metadata() -> Vec<PhotoMetadata> {
         vec![
             PhotoMetadata {
@@ -145,7 +243,11 @@ mod tests {
                     hour: 14,
                     minute: 15,
                     second: 30,
+                    offset: None,
+                    nanosecond: None,
                 },
+                source: DateSource::Exif,
+                companions: vec![],
             },
             PhotoMetadata {
                 path: PathBuf::from("photo2.jpg"),
@@ -156,7 +258,11 @@ mod tests {
                     hour: 10,
                     minute: 30,
                     second: 45,
+                    offset: None,
+                    nanosecond: None,
                 },
+                source: DateSource::Exif,
+                companions: vec![],
             },
             PhotoMetadata {
                 path: PathBuf::from("photo3.gif"),
@@ -167,7 +273,11 @@ mod tests {
                     hour: 8,
                     minute: 0,
                     second: 0,
+                    offset: None,
+                    nanosecond: None,
                 },
+                source: DateSource::Exif,
+                companions: vec![],
             },
             PhotoMetadata {
                 path: PathBuf::from("photo4.png"),
@@ -178,7 +288,11 @@ mod tests {
                     hour: 14,
                     minute: 15,
                     second: 30,
+                    offset: None,
+                    nanosecond: None,
                 },
+                source: DateSource::Exif,
+                companions: vec![],
             },
         ]
     }
@@ -187,7 +301,7 @@ mod tests {
     fn test_daily_mode_with_rename() {
         let args = create_test_args(Mode::Daily, true, 100);
         let metadata = create_test_metadata();
-        let result = from_to_paths(metadata, &args);
+        let result = paths_for(metadata, &args);
 
         assert_eq!(result.len(), 4);
         assert_eq!(result[0].0, PathBuf::from("photo1.png"));
@@ -216,7 +330,7 @@ mod tests {
     fn test_daily_mode_without_rename() {
         let args = create_test_args(Mode::Daily, false, 100);
         let metadata = create_test_metadata();
-        let result = from_to_paths(metadata, &args);
+        let result = paths_for(metadata, &args);
 
         assert_eq!(result.len(), 4);
         assert_eq!(result[0].0, PathBuf::from("photo1.png"));
@@ -233,7 +347,7 @@ mod tests {
     fn test_monthly_mode_with_rename() {
         let args = create_test_args(Mode::Monthly, true, 100);
         let metadata = create_test_metadata();
-        let result = from_to_paths(metadata, &args);
+        let result = paths_for(metadata, &args);
 
         assert_eq!(result.len(), 4);
         assert_eq!(result[0].0, PathBuf::from("photo1.png"));
@@ -262,7 +376,7 @@ mod tests {
     fn test_monthly_mode_without_rename() {
         let args = create_test_args(Mode::Monthly, false, 100);
         let metadata = create_test_metadata();
-        let result = from_to_paths(metadata, &args);
+        let result = paths_for(metadata, &args);
 
         assert_eq!(result.len(), 4);
         assert_eq!(result[0].0, PathBuf::from("photo1.png"));
@@ -279,7 +393,7 @@ mod tests {
     fn test_compact_mode_under_limit() {
         let args = create_test_args(Mode::Compact, true, 100);
         let metadata = create_test_metadata();
-        let result = from_to_paths(metadata, &args);
+        let result = paths_for(metadata, &args);
 
         assert_eq!(result.len(), 4);
         assert_eq!(
@@ -304,7 +418,7 @@ mod tests {
     fn test_compact_mode_over_limit() {
         let metadata = create_test_metadata();
         let args = create_test_args(Mode::Compact, true, 2);
-        let result = from_to_paths(metadata, &args);
+        let result = paths_for(metadata, &args);
 
         assert_eq!(result.len(), 4);
         assert_eq!(
@@ -329,7 +443,7 @@ mod tests {
     fn test_compact_mode_without_rename() {
         let metadata = create_test_metadata();
         let args = create_test_args(Mode::Compact, false, 2);
-        let result = from_to_paths(metadata, &args);
+        let result = paths_for(metadata, &args);
 
         assert_eq!(result.len(), 4);
         assert_eq!(result[0].1, PathBuf::from("test_dir/2025/12/photo1.png"));
@@ -338,11 +452,20 @@ mod tests {
         assert_eq!(result[3].1, PathBuf::from("test_dir/2026/02/20/photo4.png"));
     }
 
+    #[test]
+    fn test_compact_mode_photo_missed_by_counting() {
+        let args = create_test_args(Mode::Compact, false, 2);
+        let result = from_to_paths(create_test_metadata(), &args, &MonthCounts::new());
+
+        assert_eq!(result[0].1, PathBuf::from("test_dir/2025/12/photo1.png"));
+        assert_eq!(result[1].1, PathBuf::from("test_dir/2026/02/photo2.jpg"));
+    }
+
     #[test]
     fn test_flat_mode_without_rename() {
         let metadata = create_test_metadata();
         let args = create_test_args(Mode::Flat, false, 25);
-        let result = from_to_paths(metadata, &args);
+        let result = paths_for(metadata, &args);
 
         assert_eq!(result.len(), 4);
         assert_eq!(result[0].1, PathBuf::from("test_dir/photo1.png"));
@@ -355,7 +478,7 @@ mod tests {
     fn test_flat_mode_with_rename() {
         let metadata = create_test_metadata();
         let args = create_test_args(Mode::Flat, true, 25);
-        let result = from_to_paths(metadata, &args);
+        let result = paths_for(metadata, &args);
 
         assert_eq!(result.len(), 4);
         assert_eq!(result[0].1, PathBuf::from("test_dir/20251220_141530.png"));
@@ -363,4 +486,117 @@ mod tests {
         assert_eq!(result[2].1, PathBuf::from("test_dir/20260201_080000.gif"));
         assert_eq!(result[3].1, PathBuf::from("test_dir/20260220_141530.png"));
     }
+
+    #[test]
+    fn test_rename_with_milliseconds() {
+        let mut metadata = create_test_metadata();
+        metadata[0].datetime.nanosecond = Some(123_456_789);
+        let mut args = create_test_args(Mode::Flat, true, 25);
+        args.rename_format = RenameFormat::Milliseconds;
+        let result = paths_for(metadata, &args);
+
+        assert_eq!(result.len(), 4);
+        assert_eq!(
+            result[0].1,
+            PathBuf::from("test_dir/20251220_141530_123.png")
+        );
+        assert_eq!(
+            result[1].1,
+            PathBuf::from("test_dir/20260201_103045_000.jpg")
+        );
+    }
+
+    #[test]
+    fn test_file_time_subtree() {
+        let mut metadata = create_test_metadata();
+        metadata[0].source = DateSource::FileTime;
+        metadata[1].source = DateSource::FileTime;
+        let args = create_test_args(Mode::Compact, false, 2);
+        let result = paths_for(metadata, &args);
+
+        assert_eq!(result.len(), 4);
+        assert_eq!(
+            result[0].1,
+            PathBuf::from("test_dir/_mtime/2025/12/photo1.png")
+        );
+        assert_eq!(
+            result[1].1,
+            PathBuf::from("test_dir/_mtime/2026/02/photo2.jpg")
+        );
+        assert_eq!(result[2].1, PathBuf::from("test_dir/2026/02/photo3.gif"));
+        assert_eq!(result[3].1, PathBuf::from("test_dir/2026/02/photo4.png"));
+    }
+
+    #[test]
+    fn test_quarantine_paths() {
+        let args = create_test_args(Mode::Daily, true, 25);
+        let skipped: Vec<SkippedFile> =
+            ["source/a/scan.jpg", "source/IMG_1.CR2", "elsewhere/x.png"]
+                .iter()
+                .map(|path| SkippedFile {
+                    path: PathBuf::from(path),
+                    reason: crate::types::SkipReason::NoDate,
+                    detail: String::new(),
+                })
+                .collect();
+
+        let destinations: Vec<PathBuf> = quarantine_paths(&skipped, &args)
+            .into_iter()
+            .map(|(_, dst, companions)| {
+                assert!(companions.is_empty());
+                dst
+            })
+            .collect();
+        assert_eq!(
+            destinations,
+            vec![
+                PathBuf::from("test_dir/_undated/a/scan.jpg"),
+                PathBuf::from("test_dir/_undated/IMG_1.CR2"),
+                PathBuf::from("test_dir/_undated/x.png"),
+            ]
+        );
+
+        // Reorganizing the library in place keeps earlier quarantined files where they are
+        let mut args = args;
+        args.sources = vec![PathBuf::from("test_dir")];
+        let skipped = vec![SkippedFile {
+            path: PathBuf::from("test_dir/_undated/a/scan.jpg"),
+            reason: crate::types::SkipReason::NoDate,
+            detail: String::new(),
+        }];
+        assert_eq!(
+            quarantine_paths(&skipped, &args)[0].1,
+            PathBuf::from("test_dir/_undated/a/scan.jpg")
+        );
+    }
+
+    #[test]
+    fn test_filter_date_range() {
+        let mut args = create_test_args(Mode::Daily, false, 25);
+        let mut metadata = create_test_metadata();
+        metadata[1].companions = vec![PathBuf::from("photo2.xmp")];
+
+        let (kept, outside) = filter_date_range(create_test_metadata(), &args);
+        assert_eq!((kept.len(), outside), (4, 0));
+
+        args.since = NaiveDate::from_ymd_opt(2026, 2, 1);
+        args.until = NaiveDate::from_ymd_opt(2026, 2, 19);
+        let (kept, outside) = filter_date_range(metadata, &args);
+        let paths: Vec<PathBuf> = kept.into_iter().map(|md| md.path).collect();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("photo2.jpg"), PathBuf::from("photo3.gif")]
+        );
+        assert_eq!(outside, 2);
+    }
+
+    #[test]
+    fn test_filter_date_range_open_ended() {
+        let mut args = create_test_args(Mode::Daily, false, 25);
+        args.until = NaiveDate::from_ymd_opt(2025, 12, 20);
+        let (kept, outside) = filter_date_range(create_test_metadata(), &args);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].path, PathBuf::from("photo1.png"));
+        assert_eq!(outside, 3);
+    }
 }
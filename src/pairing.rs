@@ -0,0 +1,517 @@
+use crate::metadata::extract_content_identifier;
+use crate::quicktime::extract_video_content_identifier;
+use crate::takeout::{is_takeout_json, json_names};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+const RAW_EXTENSIONS: &[&str] = &[
+    "cr2", "cr3", "crw", "nef", "nrw", "arw", "srf", "sr2", "raf", "orf", "rw2", "dng", "pef",
+    "srw",
+];
+
+/// Developed formats a camera writes next to the RAW file
+const PROCESSED_EXTENSIONS: &[&str] = &["jpg", "jpeg", "heic", "heif"];
+
+/// Edit and thumbnail files that belong to a photo or video: darktable/Lightroom XMP,
+/// Apple edits, camera video thumbnails and RawTherapee profiles
+const SIDECAR_EXTENSIONS: &[&str] = &["xmp", "aae", "thm", "pp3"];
+
+/// The video half of an Apple Live Photo
+const LIVE_VIDEO_EXTENSIONS: &[&str] = &["mov"];
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_lowercase().as_str()))
+}
+
+pub fn is_raw(path: &Path) -> bool {
+    has_extension(path, RAW_EXTENSIONS)
+}
+
+/// Sidecars are never dated themselves and only travel with the file they describe
+pub fn is_sidecar(path: &Path) -> bool {
+    has_extension(path, SIDECAR_EXTENSIONS) || is_takeout_json(path)
+}
+
+/// Groups discovered files into photos that travel together: RAW+JPEG pairs,
+/// Live Photos and sidecars. Only files of the same folder are grouped, so a batch merging
+/// same-named folders of several sources never pairs files across sources.
+pub fn group_files(paths: Vec<PathBuf>) -> Vec<Vec<PathBuf>> {
+    attach_sidecars(pair_live_photos(
+        group_raw_pairs(paths),
+        extract_content_identifier,
+        extract_video_content_identifier,
+    ))
+}
+
+/// Groups a RAW file with the JPEG/HEIC files sharing its stem in the same directory.
+///
+/// Every group starts with its primary file (the RAW one when present); the rest are
+/// companions that travel with it. Files without a pair form groups of one.
+fn group_raw_pairs(paths: Vec<PathBuf>) -> Vec<Vec<PathBuf>> {
+    let mut groups: Vec<Vec<PathBuf>> = Vec::new();
+    let mut by_stem: HashMap<(PathBuf, OsString), usize> = HashMap::new();
+
+    for path in paths {
+        if !is_raw(&path) && !has_extension(&path, PROCESSED_EXTENSIONS) {
+            groups.push(vec![path]);
+            continue;
+        }
+
+        let key = match (path.parent(), path.file_stem()) {
+            (Some(parent), Some(stem)) => (parent.to_path_buf(), stem.to_os_string()),
+            _ => {
+                groups.push(vec![path]);
+                continue;
+            }
+        };
+
+        match by_stem.get(&key) {
+            Some(&index) => groups[index].push(path),
+            None => {
+                by_stem.insert(key, groups.len());
+                groups.push(vec![path]);
+            }
+        }
+    }
+
+    groups
+        .into_iter()
+        .flat_map(split_unpaired)
+        .map(|mut group| {
+            // RAW first, then the rest in a stable order
+            group.sort_by_key(|path| (!is_raw(path), path.clone()));
+            group
+        })
+        .collect()
+}
+
+/// Keeps a group together only when it is a real RAW+JPEG pair
+fn split_unpaired(group: Vec<PathBuf>) -> Vec<Vec<PathBuf>> {
+    let raw_count = group.iter().filter(|path| is_raw(path)).count();
+    if group.len() > 1 && raw_count == 1 {
+        vec![group]
+    } else {
+        group.into_iter().map(|path| vec![path]).collect()
+    }
+}
+
+/// Adds the video of a Live Photo to the group of its still, matched by the content
+/// identifier both carry or, when one of them has none, by stem in the same directory.
+/// Only directories holding both stills and videos are inspected.
+fn pair_live_photos<S, V>(
+    groups: Vec<Vec<PathBuf>>,
+    still_identifier: S,
+    video_identifier: V,
+) -> Vec<Vec<PathBuf>>
+where
+    S: Fn(&Path) -> Option<String>,
+    V: Fn(&Path) -> Option<String>,
+{
+    let single = |group: &[PathBuf], extensions: &[&str]| {
+        group.len() == 1 && has_extension(&group[0], extensions)
+    };
+    let directory = |index: usize| groups[index][0].parent().map(Path::to_path_buf);
+
+    let videos: Vec<usize> = (0..groups.len())
+        .filter(|&index| single(&groups[index], LIVE_VIDEO_EXTENSIONS))
+        .collect();
+    let video_dirs: HashSet<_> = videos
+        .iter()
+        .filter_map(|&index| directory(index))
+        .collect();
+    let stills: Vec<usize> = (0..groups.len())
+        .filter(|&index| single(&groups[index], PROCESSED_EXTENSIONS))
+        .filter(|&index| directory(index).is_some_and(|dir| video_dirs.contains(&dir)))
+        .collect();
+    if stills.is_empty() {
+        return groups;
+    }
+    let still_dirs: HashSet<_> = stills
+        .iter()
+        .filter_map(|&index| directory(index))
+        .collect();
+
+    let mut video_ids: HashMap<usize, Option<String>> = HashMap::new();
+    let mut by_identifier: HashMap<(PathBuf, String), usize> = HashMap::new();
+    let mut by_stem: HashMap<(PathBuf, OsString), usize> = HashMap::new();
+    for &index in &videos {
+        let path = &groups[index][0];
+        let Some(dir) = directory(index).filter(|dir| still_dirs.contains(dir)) else {
+            continue;
+        };
+        let id = video_identifier(path);
+        if let Some(id) = &id {
+            by_identifier
+                .entry((dir.clone(), id.clone()))
+                .or_insert(index);
+        }
+        if let Some(stem) = path.file_stem() {
+            by_stem.entry((dir, stem.to_os_string())).or_insert(index);
+        }
+        video_ids.insert(index, id);
+    }
+
+    let mut paired: HashMap<usize, usize> = HashMap::new();
+    let mut videos_taken: HashSet<usize> = HashSet::new();
+    let mut unmatched = Vec::new();
+    for &index in &stills {
+        let Some(dir) = directory(index) else {
+            continue;
+        };
+        let id = still_identifier(&groups[index][0]);
+        match id
+            .as_ref()
+            .and_then(|id| by_identifier.get(&(dir, id.clone())))
+        {
+            Some(&video) if videos_taken.insert(video) => {
+                paired.insert(index, video);
+            }
+            _ => unmatched.push((index, id)),
+        }
+    }
+
+    // Stem fallback, unless both halves carry identifiers that do not match
+    for (index, id) in unmatched {
+        let (Some(dir), Some(stem)) = (directory(index), groups[index][0].file_stem()) else {
+            continue;
+        };
+        if let Some(&video) = by_stem.get(&(dir, stem.to_os_string()))
+            && !videos_taken.contains(&video)
+            && (id.is_none() || video_ids[&video].is_none())
+        {
+            videos_taken.insert(video);
+            paired.insert(index, video);
+        }
+    }
+
+    let mut groups: Vec<Option<Vec<PathBuf>>> = groups.into_iter().map(Some).collect();
+    let mut result = Vec::with_capacity(groups.len() - videos_taken.len());
+    for index in 0..groups.len() {
+        if videos_taken.contains(&index) {
+            continue;
+        }
+        let mut group = groups[index].take().expect("every group is visited once");
+        if let Some(&video) = paired.get(&index) {
+            group.extend(groups[video].take().expect("a video is paired once"));
+        }
+        result.push(group);
+    }
+    result
+}
+
+/// Adds each sidecar to the group of the file it describes, matched by full file name
+/// (`photo.jpg.xmp`) or by stem (`photo.xmp`); Takeout JSON files are matched by the
+/// names Takeout derives from the media name. Sidecars without a match stay alone.
+fn attach_sidecars(groups: Vec<Vec<PathBuf>>) -> Vec<Vec<PathBuf>> {
+    let (sidecars, mut groups): (Vec<Vec<PathBuf>>, Vec<Vec<PathBuf>>) = groups
+        .into_iter()
+        .partition(|group| group.len() == 1 && is_sidecar(&group[0]));
+
+    let mut by_name: HashMap<(PathBuf, OsString), usize> = HashMap::new();
+    let mut by_stem: HashMap<(PathBuf, OsString), usize> = HashMap::new();
+    // JSON name -> (how specific the name is for the file, group); the most specific wins,
+    // so `IMG_1234-edited.jpg` does not take the JSON of `IMG_1234.jpg`
+    let mut by_json_name: HashMap<(PathBuf, OsString), (usize, usize)> = HashMap::new();
+    for (index, group) in groups.iter().enumerate() {
+        for path in group {
+            if let (Some(parent), Some(name), Some(stem)) =
+                (path.parent(), path.file_name(), path.file_stem())
+            {
+                by_name.insert((parent.to_path_buf(), name.to_os_string()), index);
+                by_stem
+                    .entry((parent.to_path_buf(), stem.to_os_string()))
+                    .or_insert(index);
+                for (rank, json_name) in json_names(path).into_iter().enumerate() {
+                    let entry = by_json_name
+                        .entry((parent.to_path_buf(), json_name.into()))
+                        .or_insert((rank, index));
+                    if rank < entry.0 {
+                        *entry = (rank, index);
+                    }
+                }
+            }
+        }
+    }
+
+    for sidecar in sidecars.into_iter().flatten() {
+        let found = match (sidecar.parent(), sidecar.file_name(), sidecar.file_stem()) {
+            (Some(parent), Some(name), _) if is_takeout_json(&sidecar) => by_json_name
+                .get(&(parent.to_path_buf(), name.to_os_string()))
+                .map(|(_, index)| index),
+            (Some(parent), _, Some(stem)) => {
+                let key = (parent.to_path_buf(), stem.to_os_string());
+                by_name.get(&key).or_else(|| by_stem.get(&key))
+            }
+            _ => None,
+        };
+
+        match found {
+            Some(&index) => groups[index].push(sidecar),
+            None => groups.push(vec![sidecar]),
+        }
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_raw_jpeg_pair() {
+        let groups = group_raw_pairs(paths(&["card/DSC_0001.JPG", "card/DSC_0001.NEF"]));
+        assert_eq!(
+            groups,
+            vec![paths(&["card/DSC_0001.NEF", "card/DSC_0001.JPG"])]
+        );
+    }
+
+    #[test]
+    fn test_raw_with_jpeg_and_heic() {
+        let groups = group_raw_pairs(paths(&["a/IMG_1.HEIC", "a/IMG_1.CR3", "a/IMG_1.JPG"]));
+        assert_eq!(
+            groups,
+            vec![paths(&["a/IMG_1.CR3", "a/IMG_1.HEIC", "a/IMG_1.JPG"])]
+        );
+    }
+
+    #[test]
+    fn test_different_directories_are_not_paired() {
+        let groups = group_raw_pairs(paths(&["a/DSC_0001.NEF", "b/DSC_0001.JPG"]));
+        assert_eq!(groups.len(), 2);
+    }
+
+    #[test]
+    fn test_sources_are_not_paired() {
+        // Two cards with the same folder layout, as discovery merges them into one batch
+        let groups = group_files(paths(&[
+            "card1/DCIM/DSC_0001.NEF",
+            "card2/DCIM/DSC_0002.JPG",
+            "card2/DCIM/DSC_0002.NEF",
+            "card2/DCIM/DSC_0003.JPG",
+        ]));
+        assert_eq!(groups.len(), 3);
+
+        let groups = group_files(paths(&[
+            "card1/DCIM/DSC_0001.JPG",
+            "card2/DCIM/DSC_0001.JPG",
+            "card1/DCIM/DSC_0001.NEF",
+            "card2/DCIM/DSC_0001.NEF",
+            "card2/DCIM/DSC_0001.xmp",
+        ]));
+        assert_eq!(
+            groups,
+            vec![
+                paths(&["card1/DCIM/DSC_0001.NEF", "card1/DCIM/DSC_0001.JPG"]),
+                paths(&[
+                    "card2/DCIM/DSC_0001.NEF",
+                    "card2/DCIM/DSC_0001.JPG",
+                    "card2/DCIM/DSC_0001.xmp"
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jpeg_only_stems_are_not_paired() {
+        let groups = group_raw_pairs(paths(&["a/photo.jpg", "a/photo.jpeg"]));
+        assert_eq!(
+            groups,
+            vec![paths(&["a/photo.jpg"]), paths(&["a/photo.jpeg"])]
+        );
+    }
+
+    #[test]
+    fn test_other_files_stay_alone() {
+        let groups = group_raw_pairs(paths(&["a/DSC_0001.NEF", "a/DSC_0001.mp4", "a/notes"]));
+        assert_eq!(
+            groups,
+            vec![
+                paths(&["a/DSC_0001.NEF"]),
+                paths(&["a/DSC_0001.mp4"]),
+                paths(&["a/notes"])
+            ]
+        );
+    }
+
+    mod pair_live_photos {
+        use super::*;
+
+        fn identifiers(pairs: &[(&str, &str)]) -> impl Fn(&Path) -> Option<String> {
+            let pairs: HashMap<PathBuf, String> = pairs
+                .iter()
+                .map(|(path, id)| (PathBuf::from(path), id.to_string()))
+                .collect();
+            move |path| pairs.get(path).cloned()
+        }
+
+        fn singles(names: &[&str]) -> Vec<Vec<PathBuf>> {
+            names.iter().map(|name| paths(&[name])).collect()
+        }
+
+        #[test]
+        fn test_by_identifier() {
+            let groups = pair_live_photos(
+                singles(&["a/IMG_0001.HEIC", "a/clip.mov", "a/IMG_0001_video.MOV"]),
+                identifiers(&[("a/IMG_0001.HEIC", "A")]),
+                identifiers(&[("a/IMG_0001_video.MOV", "A"), ("a/clip.mov", "B")]),
+            );
+            assert_eq!(
+                groups,
+                vec![
+                    paths(&["a/IMG_0001.HEIC", "a/IMG_0001_video.MOV"]),
+                    paths(&["a/clip.mov"])
+                ]
+            );
+        }
+
+        #[test]
+        fn test_by_stem_without_identifiers() {
+            let groups = pair_live_photos(
+                singles(&[
+                    "a/IMG_0002.MOV",
+                    "a/IMG_0002.JPG",
+                    "b/IMG_0003.JPG",
+                    "c/IMG_0003.MOV",
+                ]),
+                identifiers(&[]),
+                identifiers(&[("a/IMG_0002.MOV", "A")]),
+            );
+            assert_eq!(
+                groups,
+                vec![
+                    paths(&["a/IMG_0002.JPG", "a/IMG_0002.MOV"]),
+                    paths(&["b/IMG_0003.JPG"]),
+                    paths(&["c/IMG_0003.MOV"])
+                ]
+            );
+        }
+
+        #[test]
+        fn test_mismatched_identifiers_stay_apart() {
+            let groups = pair_live_photos(
+                singles(&["a/IMG_0004.HEIC", "a/IMG_0004.MOV"]),
+                identifiers(&[("a/IMG_0004.HEIC", "A")]),
+                identifiers(&[("a/IMG_0004.MOV", "B")]),
+            );
+            assert_eq!(groups.len(), 2);
+        }
+
+        #[test]
+        fn test_video_pairs_once() {
+            let groups = pair_live_photos(
+                singles(&["a/one.jpg", "a/two.jpg", "a/clip.mov"]),
+                identifiers(&[("a/one.jpg", "A"), ("a/two.jpg", "A")]),
+                identifiers(&[("a/clip.mov", "A")]),
+            );
+            assert_eq!(
+                groups,
+                vec![paths(&["a/one.jpg", "a/clip.mov"]), paths(&["a/two.jpg"])]
+            );
+        }
+    }
+
+    #[test]
+    fn test_sidecar_by_full_name() {
+        let groups = group_files(paths(&["a/photo.jpg.xmp", "a/photo.jpg", "a/photo.png"]));
+        assert_eq!(
+            groups,
+            vec![
+                paths(&["a/photo.jpg", "a/photo.jpg.xmp"]),
+                paths(&["a/photo.png"])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sidecars_by_stem() {
+        let groups = group_files(paths(&[
+            "a/IMG_0001.AAE",
+            "a/IMG_0001.HEIC",
+            "b/MVI_0002.THM",
+            "b/MVI_0002.MOV",
+        ]));
+        assert_eq!(
+            groups,
+            vec![
+                paths(&["a/IMG_0001.HEIC", "a/IMG_0001.AAE"]),
+                paths(&["b/MVI_0002.MOV", "b/MVI_0002.THM"])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sidecar_joins_raw_jpeg_pair() {
+        let groups = group_files(paths(&[
+            "a/DSC_0001.JPG",
+            "a/DSC_0001.NEF.pp3",
+            "a/DSC_0001.NEF",
+            "a/DSC_0001.xmp",
+        ]));
+        assert_eq!(
+            groups,
+            vec![paths(&[
+                "a/DSC_0001.NEF",
+                "a/DSC_0001.JPG",
+                "a/DSC_0001.NEF.pp3",
+                "a/DSC_0001.xmp"
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_takeout_json() {
+        let groups = group_files(paths(&[
+            "t/IMG_1234.jpg.json",
+            "t/IMG_1234.jpg",
+            "t/IMG_1234(1).jpg",
+            "t/IMG_1234.jpg(1).json",
+            "t/metadata.json",
+        ]));
+        assert_eq!(
+            groups,
+            vec![
+                paths(&["t/IMG_1234.jpg", "t/IMG_1234.jpg.json"]),
+                paths(&["t/IMG_1234(1).jpg", "t/IMG_1234.jpg(1).json"]),
+                paths(&["t/metadata.json"])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_takeout_json_of_edited_copy() {
+        // The edited copy sorts first, yet the JSON belongs to the original
+        let groups = group_files(paths(&[
+            "t/IMG_1234-edited.jpg",
+            "t/IMG_1234.jpg",
+            "t/IMG_1234.jpg.json",
+            "t/IMG_5678-edited.jpg",
+            "t/IMG_5678.jpg.json",
+        ]));
+        assert_eq!(
+            groups,
+            vec![
+                paths(&["t/IMG_1234-edited.jpg"]),
+                paths(&["t/IMG_1234.jpg", "t/IMG_1234.jpg.json"]),
+                paths(&["t/IMG_5678-edited.jpg", "t/IMG_5678.jpg.json"])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_orphan_sidecar_stays_alone() {
+        let groups = group_files(paths(&["a/photo.xmp", "b/photo.jpg"]));
+        assert_eq!(
+            groups,
+            vec![paths(&["b/photo.jpg"]), paths(&["a/photo.xmp"])]
+        );
+    }
+}
@@ -1,53 +1,173 @@
-use crate::discovery::discover_files;
+use crate::discovery::{Placed, discover_batches};
 use crate::metadata::paths_to_metadata;
-use crate::organizer::from_to_paths;
-use crate::setup::{init_logger, need_progress_bar, validate_io_dirs};
+use crate::organizer::{
+    MonthCounts, count_per_month, filter_date_range, from_to_paths, quarantine_paths,
+};
+use crate::pairing::group_files;
+use crate::report::SkipReport;
+use crate::setup::{init_logger, need_progress_bar, reorganizes_library, validate_io_dirs};
 use crate::transfer::transfer_multiple;
-use crate::types::Args;
+use crate::types::{Args, DateSource, Mode, PhotoMetadata, SkipReason, SkippedFile};
 
 use indicatif::{ProgressBar, ProgressStyle};
 
-use log::info;
+use log::{info, warn};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::sync::mpsc::sync_channel;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Directory batches waiting between pipeline stages; bounds memory on huge archives
+const QUEUE_DEPTH: usize = 4;
+
+/// Files discovered and time spent reading their metadata
+#[derive(Default)]
+struct ReadStats {
+    files: usize,
+    elapsed: Duration,
+}
 
 pub fn process(args: Args) -> Result<String, Box<dyn std::error::Error>> {
     validate_io_dirs(&args)?;
     init_logger(&args)?;
 
-    let paths = discover_files(args.source.clone());
-    let all_files_count: usize = paths.len();
+    let pool = ThreadPoolBuilder::new().num_threads(args.threads).build()?;
 
-    let metadata_vec = paths_to_metadata(paths);
+    // Created up front so a bad path fails before anything is moved
+    let mut report = args
+        .skip_report
+        .as_ref()
+        .map(|path| File::create(path).and_then(|file| SkipReport::create(path, file)))
+        .transpose()?;
 
-    let path_pairs = from_to_paths(metadata_vec, &args);
-    let skipped = all_files_count - path_pairs.len();
+    // Compact mode decides between monthly and daily folders by how many photos a month
+    // holds, so every photo is counted before any is placed. Metadata is still read once:
+    // the dated batches wait in memory until the count is complete
+    let mut month_counts = MonthCounts::new();
+    let mut counted = Vec::new();
+    let counting_stats = matches!(args.mode, Mode::Compact).then(|| {
+        stream_metadata(&args, &pool, None, |metadata, skipped| {
+            let (metadata, outside) = filter_date_range(metadata, &args);
+            count_per_month(&metadata, &mut month_counts);
+            counted.push((metadata, outside, skipped));
+        })
+    });
 
-    let with_progress_bar = need_progress_bar(&args);
+    // Reorganizing the library in place moves files into folders the walk has yet to
+    // visit; compact mode has finished walking by the time anything moves
+    let placed = (reorganizes_library(&args) && counting_stats.is_none())
+        .then(|| Placed::new(&args.library));
 
-    let pb = if with_progress_bar {
+    let pb = if need_progress_bar(&args) {
         Some(
-            ProgressBar::new(path_pairs.len() as u64)
+            ProgressBar::no_length()
                 .with_message("Moving files")
                 .with_style(
-                    ProgressStyle::default_bar()
-                        .template("[{bar:40}] {pos}/{len} - {msg}")
+                    ProgressStyle::default_spinner()
+                        .template("{spinner} {pos} photos - {msg}")
                         .unwrap(),
                 ),
         )
     } else {
         None
     };
-    let (transferred, already_organized, failed) =
-        transfer_multiple(path_pairs, args.dry_run, args.action, &pb);
 
+    let mut sources = BTreeMap::new();
+    let mut skip_reasons = BTreeMap::new();
+    let (mut transferred, mut already_organized, mut failed, mut quarantined) = (0, 0, 0, 0);
+    let mut outside_range = 0;
+    let mut place = |metadata: Vec<PhotoMetadata>, outside: usize, skipped: Vec<SkippedFile>| {
+        outside_range += outside;
+        count_sources(&metadata, &mut sources);
+        for file in &skipped {
+            *skip_reasons.entry(file.reason).or_insert(0) += 1;
+        }
+
+        let path_groups = from_to_paths(metadata, &args, &month_counts);
+
+        let (t, a, f) =
+            transfer_multiple(path_groups, args.dry_run, args.action, &pb, placed.as_ref());
+        transferred += t;
+        already_organized += a;
+        failed += f;
+
+        if args.quarantine {
+            let path_groups = quarantine_paths(&skipped, &args);
+            let (t, a, f) =
+                transfer_multiple(path_groups, args.dry_run, args.action, &pb, placed.as_ref());
+            quarantined += t;
+            already_organized += a;
+            failed += f;
+        }
+        // Files keep moving when the report cannot be written; it is dropped instead
+        if let Some(writer) = &mut report
+            && let Err(err) = writer.write(&skipped)
+        {
+            warn!("Stopped writing the skip report: {}", err);
+            report = None;
+        }
+    };
+    let stats = match counting_stats {
+        Some(stats) => {
+            for (metadata, outside, skipped) in counted {
+                place(metadata, outside, skipped);
+            }
+            stats
+        }
+        None => stream_metadata(&args, &pool, placed.as_ref(), |metadata, skipped| {
+            let (metadata, outside) = filter_date_range(metadata, &args);
+            place(metadata, outside, skipped)
+        }),
+    };
+
+    if let Some(report) = report
+        && let Err(err) = report.finish()
+    {
+        warn!("Failed to finish the skip report: {}", err);
+    }
+
+    let skipped: usize = skip_reasons.values().sum();
+    let range_note = if args.since.is_some() || args.until.is_some() {
+        format!(", {} outside the date range", outside_range)
+    } else {
+        String::new()
+    };
+    let skip_note = skip_reasons_note(&skip_reasons);
+    let quarantine_note = match (args.quarantine, args.dry_run) {
+        (false, _) => String::new(),
+        (true, false) => format!(", {} quarantined", quarantined),
+        (true, true) => format!(", {} would be quarantined", quarantined),
+    };
+    let fallback_note = fallback_dates_note(&sources);
+    let throughput = throughput_note(&stats, pool.current_num_threads());
     let summary = if args.dry_run {
         format!(
-            "[DRY RUN] Processed {} files: {} would be transferred, {} were already organized, {} skipped (no EXIF)",
-            all_files_count, transferred, already_organized, skipped
+            "[DRY RUN] Processed {} files: {} would be transferred, {} were already organized{}, {} skipped{}{}{}\n{}",
+            stats.files,
+            transferred,
+            already_organized,
+            range_note,
+            skipped,
+            skip_note,
+            quarantine_note,
+            fallback_note,
+            throughput
         )
     } else {
         format!(
-            "Processed {} files: {} transferred, {} were already organized, {} skipped (no EXIF), {} failed",
-            all_files_count, transferred, already_organized, skipped, failed
+            "Processed {} files: {} transferred, {} were already organized{}, {} skipped{}{}, {} failed{}\n{}",
+            stats.files,
+            transferred,
+            already_organized,
+            range_note,
+            skipped,
+            skip_note,
+            quarantine_note,
+            failed,
+            fallback_note,
+            throughput
         )
     };
 
@@ -59,3 +179,97 @@ pub fn process(args: Args) -> Result<String, Box<dyn std::error::Error>> {
 
     Ok(summary)
 }
+
+/// Runs discovery, grouping and dating as a pipeline of bounded stages, handing each
+/// directory's dated photos and skipped files to `consume` on the calling thread as soon
+/// as they are ready. `placed` keeps the walk off files already moved into the library.
+fn stream_metadata<F>(
+    args: &Args,
+    pool: &ThreadPool,
+    placed: Option<&Placed>,
+    mut consume: F,
+) -> ReadStats
+where
+    F: FnMut(Vec<PhotoMetadata>, Vec<SkippedFile>),
+{
+    let mut stats = ReadStats::default();
+
+    thread::scope(|scope| {
+        let (batch_tx, batch_rx) = sync_channel(QUEUE_DEPTH);
+        let (metadata_tx, metadata_rx) = sync_channel(QUEUE_DEPTH);
+
+        scope.spawn(move || {
+            for batch in discover_batches(args, placed) {
+                if batch_tx.send(batch).is_err() {
+                    break;
+                }
+            }
+        });
+
+        scope.spawn(move || {
+            for batch in batch_rx {
+                let files = batch.len();
+                let started = Instant::now();
+                let (metadata, skipped) =
+                    pool.install(|| paths_to_metadata(group_files(batch), args));
+                if metadata_tx
+                    .send((files, metadata, skipped, started.elapsed()))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        for (files, metadata, skipped, elapsed) in metadata_rx {
+            stats.files += files;
+            stats.elapsed += elapsed;
+            consume(metadata, skipped);
+        }
+    });
+
+    stats
+}
+
+fn count_sources(metadata: &[PhotoMetadata], counts: &mut BTreeMap<DateSource, usize>) {
+    for md in metadata.iter().filter(|md| md.source != DateSource::Exif) {
+        *counts.entry(md.source).or_insert(0) += 1;
+    }
+}
+
+fn skip_reasons_note(counts: &BTreeMap<SkipReason, usize>) -> String {
+    if counts.is_empty() {
+        return String::new();
+    }
+
+    let parts: Vec<String> = counts
+        .iter()
+        .map(|(reason, count)| format!("{}: {}", reason, count))
+        .collect();
+    format!(" ({})", parts.join(", "))
+}
+
+fn fallback_dates_note(counts: &BTreeMap<DateSource, usize>) -> String {
+    if counts.is_empty() {
+        return String::new();
+    }
+
+    let parts: Vec<String> = counts
+        .iter()
+        .map(|(source, count)| format!("{} from {}", count, source))
+        .collect();
+    format!(" (dated without EXIF: {})", parts.join(", "))
+}
+
+fn throughput_note(stats: &ReadStats, threads: usize) -> String {
+    let seconds = stats.elapsed.as_secs_f64();
+    let rate = if seconds > 0.0 {
+        stats.files as f64 / seconds
+    } else {
+        0.0
+    };
+    format!(
+        "Read metadata of {} files in {:.1}s ({:.0} files/s, threads: {})",
+        stats.files, seconds, rate, threads
+    )
+}
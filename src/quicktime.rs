@@ -0,0 +1,336 @@
+use crate::metadata::ExifError;
+use crate::types::PhotoDateTime;
+use chrono::{DateTime, Datelike, FixedOffset, Local};
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "mov", "3gp", "3g2"];
+
+/// Seconds between the QuickTime epoch (1904-01-01 UTC) and the Unix epoch
+const QUICKTIME_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// Upper bound for the `moov` atom we are willing to load into memory
+const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+
+const APPLE_CREATION_DATE_KEY: &[u8] = b"com.apple.quicktime.creationdate";
+
+/// Links a Live Photo video to its still
+const APPLE_CONTENT_IDENTIFIER_KEY: &[u8] = b"com.apple.quicktime.content.identifier";
+
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Reads the capture time of an MP4/MOV/3GP file.
+///
+/// Prefers Apple's `com.apple.quicktime.creationdate`, which keeps the local time and offset,
+/// over `mvhd` creation time, which is UTC and converted to the system time zone.
+pub fn extract_video_datetime(path: &Path) -> Result<PhotoDateTime, ExifError> {
+    let mut file = File::open(path)?;
+    let moov = read_moov(&mut file)?.ok_or(ExifError::NoVideoDateError)?;
+
+    apple_creation_date(&moov)
+        .or_else(|| mvhd_creation_time(&moov))
+        .ok_or(ExifError::NoVideoDateError)
+}
+
+/// Reads the Live Photo content identifier of a MOV file
+pub fn extract_video_content_identifier(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let moov = read_moov(&mut file).ok()??;
+    apple_metadata(&moov, APPLE_CONTENT_IDENTIFIER_KEY)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// Walks top-level atoms without reading media data and returns the body of `moov`
+pub(crate) fn read_moov<R: Read + Seek>(reader: &mut R) -> Result<Option<Vec<u8>>, std::io::Error> {
+    loop {
+        let mut header = [0u8; 8];
+        match reader.read_exact(&mut header) {
+            Ok(()) => (),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let kind = [header[4], header[5], header[6], header[7]];
+        let (size, header_len) =
+            match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+                0 if &kind == b"moov" => {
+                    let mut body = Vec::new();
+                    reader.take(MAX_MOOV_SIZE).read_to_end(&mut body)?;
+                    return Ok(Some(body));
+                }
+                0 => return Ok(None),
+                1 => {
+                    let mut large_size = [0u8; 8];
+                    reader.read_exact(&mut large_size)?;
+                    (u64::from_be_bytes(large_size), 16)
+                }
+                size => (size as u64, 8),
+            };
+
+        let Some(body_len) = size.checked_sub(header_len) else {
+            return Ok(None);
+        };
+
+        if &kind == b"moov" {
+            if body_len > MAX_MOOV_SIZE {
+                return Ok(None);
+            }
+            let mut body = vec![0u8; body_len as usize];
+            reader.read_exact(&mut body)?;
+            return Ok(Some(body));
+        }
+
+        let Ok(skip) = i64::try_from(body_len) else {
+            return Ok(None);
+        };
+        reader.seek(SeekFrom::Current(skip))?;
+    }
+}
+
+/// Iterates over sibling atoms in a buffer, yielding their type and body
+pub(crate) struct Atoms<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Atoms<'a> {
+    type Item = ([u8; 4], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.len() < 8 {
+            return None;
+        }
+
+        let kind = [self.data[4], self.data[5], self.data[6], self.data[7]];
+        let (size, header_len) =
+            match u32::from_be_bytes([self.data[0], self.data[1], self.data[2], self.data[3]]) {
+                0 => (self.data.len(), 8),
+                1 => {
+                    let large_size = u64::from_be_bytes(self.data.get(8..16)?.try_into().ok()?);
+                    (usize::try_from(large_size).ok()?, 16)
+                }
+                size => (size as usize, 8),
+            };
+
+        if size < header_len || size > self.data.len() {
+            self.data = &[];
+            return None;
+        }
+
+        let body = &self.data[header_len..size];
+        self.data = &self.data[size..];
+        Some((kind, body))
+    }
+}
+
+pub(crate) fn atoms(data: &[u8]) -> Atoms<'_> {
+    Atoms { data }
+}
+
+pub(crate) fn find_atom<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    atoms(data).find(|(k, _)| k == kind).map(|(_, body)| body)
+}
+
+fn mvhd_creation_time(moov: &[u8]) -> Option<PhotoDateTime> {
+    let mvhd = find_atom(moov, b"mvhd")?;
+    let seconds = match mvhd.first()? {
+        0 => u32::from_be_bytes(mvhd.get(4..8)?.try_into().ok()?) as i64,
+        1 => i64::try_from(u64::from_be_bytes(mvhd.get(4..12)?.try_into().ok()?)).ok()?,
+        _ => return None,
+    };
+
+    // Zero means the recorder did not set the field
+    if seconds == 0 {
+        return None;
+    }
+
+    let utc = DateTime::from_timestamp(seconds - QUICKTIME_EPOCH_OFFSET, 0)?;
+    to_photo_datetime(utc.with_timezone(&Local).fixed_offset())
+}
+
+fn apple_creation_date(moov: &[u8]) -> Option<PhotoDateTime> {
+    let value = apple_metadata(moov, APPLE_CREATION_DATE_KEY)?;
+    let dt = DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%z").ok()?;
+    to_photo_datetime(dt)
+}
+
+/// Looks up a string value in the `moov/meta` key-value list Apple devices write
+fn apple_metadata<'a>(moov: &'a [u8], key: &[u8]) -> Option<&'a str> {
+    let meta = find_atom(moov, b"meta")?;
+    // QuickTime `meta` is a plain atom, ISO `meta` starts with version and flags
+    let meta = match meta.get(0..4) {
+        Some([0, 0, 0, 0]) => &meta[4..],
+        _ => meta,
+    };
+
+    let index = key_index(find_atom(meta, b"keys")?, key)?;
+    let item = atoms(find_atom(meta, b"ilst")?)
+        .find(|(kind, _)| u32::from_be_bytes(*kind) == index)
+        .map(|(_, body)| body)?;
+
+    // `data` atom: type indicator and locale, then the UTF-8 value
+    let value = find_atom(item, b"data")?.get(8..)?;
+    Some(std::str::from_utf8(value).ok()?.trim_end_matches('\0'))
+}
+
+/// Returns the 1-based index of a key in a `keys` atom, which `ilst` items refer to
+fn key_index(keys: &[u8], key: &[u8]) -> Option<u32> {
+    let count = u32::from_be_bytes(keys.get(4..8)?.try_into().ok()?);
+    let mut entries = keys.get(8..)?;
+
+    for index in 1..=count {
+        let size = u32::from_be_bytes(entries.get(0..4)?.try_into().ok()?) as usize;
+        let name = entries.get(8..size)?;
+        if name == key {
+            return Some(index);
+        }
+        entries = entries.get(size..)?;
+    }
+    None
+}
+
+fn to_photo_datetime(dt: DateTime<FixedOffset>) -> Option<PhotoDateTime> {
+    (dt.year() >= 1970).then(|| dt.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(body);
+        data
+    }
+
+    fn mvhd(version: u8, seconds: u64) -> Vec<u8> {
+        let mut body = vec![version, 0, 0, 0];
+        if version == 0 {
+            body.extend_from_slice(&(seconds as u32).to_be_bytes());
+        } else {
+            body.extend_from_slice(&seconds.to_be_bytes());
+        }
+        body.extend_from_slice(&[0; 80]);
+        atom(b"mvhd", &body)
+    }
+
+    fn apple_meta(value: &str, iso: bool) -> Vec<u8> {
+        let mut key_entry = ((APPLE_CREATION_DATE_KEY.len() + 8) as u32)
+            .to_be_bytes()
+            .to_vec();
+        key_entry.extend_from_slice(b"mdta");
+        key_entry.extend_from_slice(APPLE_CREATION_DATE_KEY);
+
+        let mut keys = vec![0, 0, 0, 0];
+        keys.extend_from_slice(&2u32.to_be_bytes());
+        keys.extend_from_slice(&[0, 0, 0, 12]);
+        keys.extend_from_slice(b"mdtaxxxx");
+        keys.extend_from_slice(&key_entry);
+
+        let mut data = vec![0, 0, 0, 1, 0, 0, 0, 0];
+        data.extend_from_slice(value.as_bytes());
+        let ilst = atom(b"ilst", &atom(&2u32.to_be_bytes(), &atom(b"data", &data)));
+
+        let mut body = if iso { vec![0, 0, 0, 0] } else { vec![] };
+        body.extend(atom(b"keys", &keys));
+        body.extend(ilst);
+        atom(b"meta", &body)
+    }
+
+    fn ymd_hms(dt: PhotoDateTime) -> (u16, u8, u8, u8, u8, u8) {
+        (dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second)
+    }
+
+    #[test]
+    fn test_is_video() {
+        assert!(is_video(Path::new("clip.mp4")));
+        assert!(is_video(Path::new("IMG_0001.MOV")));
+        assert!(is_video(Path::new("old/phone.3gp")));
+        assert!(!is_video(Path::new("photo.jpg")));
+        assert!(!is_video(Path::new("mov")));
+    }
+
+    #[test]
+    fn test_apple_creation_date_keeps_local_time() {
+        let moov = apple_meta("2025-07-15T01:30:00+0900", false);
+        let dt = apple_creation_date(&moov).unwrap();
+        assert_eq!(ymd_hms(dt), (2025, 7, 15, 1, 30, 0));
+        assert_eq!(dt.offset, Some(540));
+    }
+
+    #[test]
+    fn test_apple_creation_date_in_iso_meta() {
+        let moov = apple_meta("2024-02-29T23:59:59-0500", true);
+        let dt = apple_creation_date(&moov).unwrap();
+        assert_eq!(ymd_hms(dt), (2024, 2, 29, 23, 59, 59));
+        assert_eq!(dt.offset, Some(-300));
+    }
+
+    #[test]
+    fn test_apple_creation_date_malformed() {
+        assert!(apple_creation_date(&apple_meta("yesterday", false)).is_none());
+    }
+
+    #[test]
+    fn test_apple_metadata_unknown_key() {
+        let moov = apple_meta("2025-07-15T01:30:00+0900", false);
+        assert_eq!(
+            apple_metadata(&moov, APPLE_CREATION_DATE_KEY),
+            Some("2025-07-15T01:30:00+0900")
+        );
+        assert!(apple_metadata(&moov, APPLE_CONTENT_IDENTIFIER_KEY).is_none());
+    }
+
+    #[test]
+    fn test_mvhd_epoch() {
+        // 1970-01-02 00:00:00 UTC
+        let moov = mvhd(0, QUICKTIME_EPOCH_OFFSET as u64 + 86_400);
+        let dt = mvhd_creation_time(&moov).unwrap();
+        let expected = DateTime::from_timestamp(86_400, 0)
+            .unwrap()
+            .with_timezone(&Local);
+        assert_eq!(
+            ymd_hms(dt),
+            ymd_hms(to_photo_datetime(expected.fixed_offset()).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_mvhd_version_1() {
+        let moov = mvhd(1, QUICKTIME_EPOCH_OFFSET as u64 + 1_750_000_000);
+        assert!(mvhd_creation_time(&moov).is_some());
+    }
+
+    #[test]
+    fn test_mvhd_unset() {
+        assert!(mvhd_creation_time(&mvhd(0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_read_moov_skips_media_data() {
+        let mut file = atom(b"ftyp", b"isom");
+        file.extend(atom(b"mdat", &[0; 256]));
+        file.extend(atom(b"moov", &mvhd(0, 1)));
+        let moov = read_moov(&mut std::io::Cursor::new(file)).unwrap();
+        assert_eq!(moov, Some(mvhd(0, 1)));
+    }
+
+    #[test]
+    fn test_read_moov_missing() {
+        let file = atom(b"ftyp", b"isom");
+        assert_eq!(read_moov(&mut std::io::Cursor::new(file)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_truncated_atoms() {
+        let mut data = atom(b"mvhd", &[0; 16]);
+        data.truncate(12);
+        assert_eq!(atoms(&data).count(), 0);
+    }
+}
@@ -0,0 +1,514 @@
+use crate::metadata::ExifError;
+use crate::quicktime::{atoms, find_atom, read_moov};
+use exif::{Exif, Reader};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Canon's `uuid` box inside `moov` that holds the CR3 metadata
+const CR3_METADATA_UUID: [u8; 16] = [
+    0x85, 0xc0, 0xb6, 0x87, 0x82, 0x0f, 0x11, 0xe0, 0x81, 0x11, 0xf4, 0xce, 0x46, 0x2b, 0x6a, 0x48,
+];
+
+/// Offset of the embedded JPEG pointer in a RAF header
+const RAF_JPEG_POINTER: u64 = 84;
+
+/// Values larger than this (embedded previews, huge maker notes) are not read
+const MAX_VALUE_SIZE: u64 = 1024 * 1024;
+
+/// Signature opening the APP1 segment that holds the XMP packet of a JPEG
+const JPEG_XMP_SIGNATURE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// IFD entries pointing to the Exif, GPS and Interoperability IFDs
+const IFD_POINTER_TAGS: &[u16] = &[0x8769, 0x8825, 0xa005];
+
+/// Containers read with bounded I/O: just the region holding the metadata is loaded
+enum Container {
+    Jpeg,
+    /// TIFF-based RAW (CR2, NEF, ARW, DNG, PEF) and plain TIFF
+    Tiff,
+    /// TIFF with a vendor magic number instead of 42 (ORF, RW2)
+    PatchedTiff,
+    /// Fujifilm RAF with an embedded JPEG
+    Raf,
+    /// Canon CR3, an ISO base media file
+    Cr3,
+}
+
+/// Reads EXIF from an image, including RAW containers.
+///
+/// JPEG, TIFF-based RAW, RAF and CR3 are parsed from just their metadata region, so a
+/// multi-hundred-megabyte file costs a few small reads. Other formats (PNG, HEIF, WebP)
+/// go through `read_from_container`. CR3 stores IFD0 and the Exif IFD as separate TIFF
+/// streams, so several `Exif` may be returned.
+pub fn read_exif(path: &Path) -> Result<Vec<Exif>, ExifError> {
+    let mut file = File::open(path)?;
+    let mut head = [0u8; 16];
+    let head_len = file.read(&mut head)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let reader = Reader::new();
+    match detect(&head[..head_len]) {
+        None => Ok(vec![reader.read_from_container(&mut BufReader::new(file))?]),
+        Some(Container::Jpeg) => Ok(vec![reader.read_raw(read_jpeg_exif(&mut file, 0)?)?]),
+        Some(Container::Tiff) => Ok(vec![reader.read_raw(read_tiff_header(&mut file)?)?]),
+        Some(Container::PatchedTiff) => {
+            let mut data = read_tiff_header(&mut file)?;
+            let magic: [u8; 2] = if data[0] == b'I' { [42, 0] } else { [0, 42] };
+            data[2..4].copy_from_slice(&magic);
+            Ok(vec![reader.read_raw(data)?])
+        }
+        Some(Container::Raf) => {
+            let offset = raf_jpeg_offset(&mut file)?;
+            Ok(vec![reader.read_raw(read_jpeg_exif(&mut file, offset)?)?])
+        }
+        Some(Container::Cr3) => {
+            let streams = read_cr3_tiff_streams(&mut file)?;
+            if streams.is_empty() {
+                return Err(ExifError::NoDataError);
+            }
+            streams
+                .into_iter()
+                .map(|tiff| reader.read_raw(tiff).map_err(ExifError::from))
+                .collect()
+        }
+    }
+}
+
+/// Reads the XMP packet of a JPEG from its APP1 segment, without touching the image data.
+/// `Ok(None)` when the file is not a JPEG.
+pub fn read_jpeg_xmp(path: &Path) -> Result<Option<Vec<u8>>, ExifError> {
+    let mut file = File::open(path)?;
+    let mut head = [0u8; 4];
+    let head_len = file.read(&mut head)?;
+    if !matches!(detect(&head[..head_len]), Some(Container::Jpeg)) {
+        return Ok(None);
+    }
+    read_jpeg_app1(&mut file, 0, JPEG_XMP_SIGNATURE).map(Some)
+}
+
+fn detect(head: &[u8]) -> Option<Container> {
+    if head.starts_with(b"FUJIFILMCCD-RAW") {
+        return Some(Container::Raf);
+    }
+
+    match head.get(0..4) {
+        Some(b"IIRO") | Some(b"IIRS") | Some(b"MMOR") | Some(b"IIU\0") => {
+            return Some(Container::PatchedTiff);
+        }
+        Some(b"II*\0") | Some(b"MM\0*") => return Some(Container::Tiff),
+        Some([0xff, 0xd8, 0xff, _]) => return Some(Container::Jpeg),
+        _ => (),
+    }
+
+    match (head.get(4..8), head.get(8..12)) {
+        (Some(b"ftyp"), Some(b"crx ")) => Some(Container::Cr3),
+        _ => None,
+    }
+}
+
+/// Walks the JPEG markers starting at `start` and returns the TIFF data of the Exif APP1
+/// segment
+fn read_jpeg_exif<R: Read + Seek>(reader: &mut R, start: u64) -> Result<Vec<u8>, ExifError> {
+    read_jpeg_app1(reader, start, b"Exif\0\0")
+}
+
+/// Walks the JPEG markers starting at `start` and returns the body of the first APP1
+/// segment opening with `signature`, without it, skipping over every other segment
+/// without reading it
+fn read_jpeg_app1<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    signature: &[u8],
+) -> Result<Vec<u8>, ExifError> {
+    reader.seek(SeekFrom::Start(start))?;
+    let mut soi = [0u8; 2];
+    reader.read_exact(&mut soi)?;
+    if soi != [0xff, 0xd8] {
+        return Err(ExifError::NoDataError);
+    }
+
+    loop {
+        let mut marker = [0u8; 2];
+        reader.read_exact(&mut marker)?;
+        if marker[0] != 0xff {
+            return Err(ExifError::NoDataError);
+        }
+        match marker[1] {
+            // Fill byte, then the marker follows
+            0xff => {
+                reader.seek(SeekFrom::Current(-1))?;
+                continue;
+            }
+            // Standalone markers carry no length
+            0x01 | 0xd0..=0xd7 => continue,
+            // Start of scan or end of image: no Exif before the image data
+            0xda | 0xd9 => return Err(ExifError::NoDataError),
+            _ => (),
+        }
+
+        let mut length = [0u8; 2];
+        reader.read_exact(&mut length)?;
+        let Some(body_len) = u16::from_be_bytes(length).checked_sub(2) else {
+            return Err(ExifError::NoDataError);
+        };
+
+        if marker[1] == 0xe1 {
+            let mut body = vec![0u8; body_len as usize];
+            reader.read_exact(&mut body)?;
+            if let Some(data) = body.strip_prefix(signature) {
+                return Ok(data.to_vec());
+            }
+        } else {
+            reader.seek(SeekFrom::Current(i64::from(body_len)))?;
+        }
+    }
+}
+
+/// Loads the TIFF header, IFD0 and the Exif, GPS and Interoperability IFDs with their
+/// values, seeking to each structure instead of reading the image data in between.
+///
+/// The structures are packed one after another and their offsets rewritten, so the result
+/// parses as a regular TIFF no matter where the file keeps them: libtiff, for one, writes
+/// the IFDs after the image data. Values too large to read are emptied, and IFD0 is cut
+/// from the IFD chain as only the primary image matters here.
+fn read_tiff_header<R: Read + Seek>(reader: &mut R) -> Result<Vec<u8>, ExifError> {
+    let mut header = TiffHeader {
+        file_len: reader.seek(SeekFrom::End(0))?,
+        reader,
+        big_endian: false,
+        data: Vec::new(),
+        visited: Vec::new(),
+    };
+
+    header.read_at(0, 8)?;
+    header.big_endian = header.data[0] == b'M';
+    let ifd0 = header.walk_ifd(header.u32_at(4))?;
+    header.put_u32(4, ifd0);
+    let next_pointer = ifd0 + 2 + header.u16_at(ifd0) * 12;
+    header.put_u32(next_pointer, 0);
+    Ok(header.data)
+}
+
+struct TiffHeader<'a, R> {
+    reader: &'a mut R,
+    file_len: u64,
+    big_endian: bool,
+    /// Structures read so far, packed in the order they were read
+    data: Vec<u8>,
+    visited: Vec<u64>,
+}
+
+impl<R: Read + Seek> TiffHeader<'_, R> {
+    /// Appends a structure of the file to `data`, returning its offset there
+    fn read_at(&mut self, offset: u64, len: u64) -> Result<u64, ExifError> {
+        if offset
+            .checked_add(len)
+            .is_none_or(|end| end > self.file_len)
+        {
+            return Err(ExifError::NoDataError);
+        }
+        let start = self.data.len();
+        self.data.resize(start + len as usize, 0);
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.reader.read_exact(&mut self.data[start..])?;
+        Ok(start as u64)
+    }
+
+    fn u16_at(&self, at: u64) -> u64 {
+        let at = at as usize;
+        let bytes = [self.data[at], self.data[at + 1]];
+        u64::from(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32_at(&self, at: u64) -> u64 {
+        let at = at as usize;
+        let bytes = [
+            self.data[at],
+            self.data[at + 1],
+            self.data[at + 2],
+            self.data[at + 3],
+        ];
+        u64::from(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn put_u32(&mut self, at: u64, value: u64) {
+        let value = value as u32;
+        let bytes = if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.data[at as usize..at as usize + 4].copy_from_slice(&bytes);
+    }
+
+    /// Reads an IFD, its values and the IFDs it points to, pointing the entries at their
+    /// new offsets; returns the offset of the IFD in `data`
+    fn walk_ifd(&mut self, offset: u64) -> Result<u64, ExifError> {
+        // Guard against pointer loops in malformed files
+        if self.visited.contains(&offset) || self.visited.len() >= 8 {
+            return Err(ExifError::NoDataError);
+        }
+        self.visited.push(offset);
+
+        let ifd = self.read_at(offset, 2)?;
+        let count = self.u16_at(ifd);
+        self.read_at(offset + 2, count * 12 + 4)?;
+
+        for entry in (0..count).map(|index| ifd + 2 + index * 12) {
+            let tag = self.u16_at(entry) as u16;
+            let value_len = type_size(self.u16_at(entry + 2) as u16) * self.u32_at(entry + 4);
+            let value = self.u32_at(entry + 8);
+
+            if IFD_POINTER_TAGS.contains(&tag) {
+                let child = self.walk_ifd(value)?;
+                self.put_u32(entry + 8, child);
+            } else if value_len > 4 {
+                if value_len <= MAX_VALUE_SIZE && value + value_len <= self.file_len {
+                    let moved = self.read_at(value, value_len)?;
+                    self.put_u32(entry + 8, moved);
+                } else {
+                    // Values too large or past the end of the file are left out, emptied
+                    self.put_u32(entry + 4, 0);
+                }
+            }
+        }
+
+        Ok(ifd)
+    }
+}
+
+/// Size in bytes of one value of a TIFF field type
+fn type_size(field_type: u16) -> u64 {
+    match field_type {
+        1 | 2 | 6 | 7 => 1,
+        3 | 8 => 2,
+        4 | 9 | 11 | 13 => 4,
+        5 | 10 | 12 => 8,
+        _ => 0,
+    }
+}
+
+fn raf_jpeg_offset<R: Read + Seek>(reader: &mut R) -> Result<u64, ExifError> {
+    let mut pointer = [0u8; 4];
+    reader.seek(SeekFrom::Start(RAF_JPEG_POINTER))?;
+    reader.read_exact(&mut pointer)?;
+    Ok(u64::from(u32::from_be_bytes(pointer)))
+}
+
+/// Returns the TIFF streams of the `CMT1` (IFD0) and `CMT2` (Exif IFD) boxes
+fn read_cr3_tiff_streams<R: Read + Seek>(reader: &mut R) -> Result<Vec<Vec<u8>>, ExifError> {
+    let Some(moov) = read_moov(reader)? else {
+        return Ok(vec![]);
+    };
+
+    let Some(canon) = atoms(&moov)
+        .filter(|(kind, _)| kind == b"uuid")
+        .map(|(_, body)| body)
+        .find(|body| body.starts_with(&CR3_METADATA_UUID))
+    else {
+        return Ok(vec![]);
+    };
+
+    let boxes = &canon[CR3_METADATA_UUID.len()..];
+    Ok([b"CMT1", b"CMT2"]
+        .iter()
+        .filter_map(|kind| find_atom(boxes, kind))
+        .map(|tiff| tiff.to_vec())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn jpeg_with_exif(tiff: &[u8]) -> Vec<u8> {
+        let mut jpeg = vec![0xff, 0xd8];
+        // APP0 and an XMP APP1 come before the Exif segment
+        jpeg.extend_from_slice(&[0xff, 0xe0, 0x00, 0x06, b'J', b'F', b'I', b'F']);
+        jpeg.extend_from_slice(&[0xff, 0xe1, 0x00, 0x06, b'h', b't', b't', b'p']);
+        let mut app1 = b"Exif\0\0".to_vec();
+        app1.extend_from_slice(tiff);
+        jpeg.extend_from_slice(&[0xff, 0xe1]);
+        jpeg.extend_from_slice(&(app1.len() as u16 + 2).to_be_bytes());
+        jpeg.extend(app1);
+        jpeg.extend_from_slice(&[0xff, 0xda, 0x00, 0x02, 0xff, 0xd9]);
+        jpeg
+    }
+
+    /// Little-endian TIFF with IFD0 (Make, Exif pointer, next IFD) and an Exif IFD
+    /// holding DateTimeOriginal, followed by `padding` bytes of image data
+    fn tiff(padding: usize) -> Vec<u8> {
+        let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+        // IFD0 at 8: two entries, next IFD at 200 (a thumbnail we never read)
+        tiff.extend_from_slice(&2u16.to_le_bytes());
+        tiff.extend_from_slice(&[0x0f, 0x01, 2, 0, 6, 0, 0, 0, 38, 0, 0, 0]);
+        tiff.extend_from_slice(&[0x69, 0x87, 4, 0, 1, 0, 0, 0, 44, 0, 0, 0]);
+        tiff.extend_from_slice(&200u32.to_le_bytes());
+        tiff.extend_from_slice(b"Canon\0");
+        // Exif IFD at 44: one entry, value at 62
+        tiff.extend_from_slice(&1u16.to_le_bytes());
+        tiff.extend_from_slice(&[0x03, 0x90, 2, 0, 20, 0, 0, 0, 62, 0, 0, 0]);
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        tiff.extend_from_slice(b"2025:03:01 10:15:00\0");
+        tiff.resize(tiff.len() + padding, 0xaa);
+        tiff
+    }
+
+    #[test]
+    fn test_read_jpeg_exif_skips_other_segments() {
+        let jpeg = jpeg_with_exif(b"II*\0tiff");
+        assert_eq!(
+            read_jpeg_exif(&mut Cursor::new(jpeg), 0).unwrap(),
+            b"II*\0tiff"
+        );
+    }
+
+    #[test]
+    fn test_read_jpeg_app1_xmp() {
+        let mut jpeg = vec![0xff, 0xd8];
+        let mut app1 = JPEG_XMP_SIGNATURE.to_vec();
+        app1.extend_from_slice(b"<x:xmpmeta/>");
+        jpeg.extend_from_slice(&[0xff, 0xe1]);
+        jpeg.extend_from_slice(&(app1.len() as u16 + 2).to_be_bytes());
+        jpeg.extend(app1);
+        jpeg.extend_from_slice(&[0xff, 0xda, 0x00, 0x02, b'<', b'x']);
+
+        let mut reader = Cursor::new(jpeg);
+        assert_eq!(
+            read_jpeg_app1(&mut reader, 0, JPEG_XMP_SIGNATURE).unwrap(),
+            b"<x:xmpmeta/>"
+        );
+        assert!(read_jpeg_exif(&mut reader, 0).is_err());
+    }
+
+    #[test]
+    fn test_read_jpeg_exif_missing() {
+        let jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x02, 0xff, 0xda, 0x00, 0x02];
+        assert!(read_jpeg_exif(&mut Cursor::new(jpeg), 0).is_err());
+        assert!(read_jpeg_exif(&mut Cursor::new(b"not a jpeg".to_vec()), 0).is_err());
+    }
+
+    #[test]
+    fn test_read_tiff_header_leaves_image_data() {
+        let file = tiff(4096);
+        let data = read_tiff_header(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(data.len(), 82);
+        // The chain to IFD1 is cut
+        assert_eq!(&data[34..38], &[0, 0, 0, 0]);
+
+        let exif = Reader::new().read_raw(data).unwrap();
+        let date = exif
+            .get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)
+            .unwrap();
+        assert_eq!(date.display_value().to_string(), "2025-03-01 10:15:00");
+    }
+
+    #[test]
+    fn test_read_tiff_header_after_image_data() {
+        // The structures of `tiff` moved behind 4 KiB of image data, as libtiff writes them
+        let shift = 4096;
+        let mut file = tiff(0);
+        for at in [4, 18, 30, 34, 54] {
+            let offset = u32::from_le_bytes(file[at..at + 4].try_into().unwrap());
+            file[at..at + 4].copy_from_slice(&(offset + shift).to_le_bytes());
+        }
+        let structures = file.split_off(8);
+        file.resize(8 + shift as usize, 0xaa);
+        file.extend(structures);
+
+        let data = read_tiff_header(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(data.len(), 82);
+        let exif = Reader::new().read_raw(data).unwrap();
+        let date = exif
+            .get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)
+            .unwrap();
+        assert_eq!(date.display_value().to_string(), "2025-03-01 10:15:00");
+    }
+
+    #[test]
+    fn test_read_tiff_header_skips_large_values() {
+        let large = MAX_VALUE_SIZE as u32 + 1;
+        let mut file = tiff(large as usize);
+        // Make becomes a value larger than the limit, stored in the image data
+        file[12..14].copy_from_slice(&7u16.to_le_bytes());
+        file[14..18].copy_from_slice(&large.to_le_bytes());
+        file[18..22].copy_from_slice(&82u32.to_le_bytes());
+
+        let data = read_tiff_header(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(data.len(), 76);
+        let exif = Reader::new().read_raw(data).unwrap();
+        assert!(
+            exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_read_tiff_header_pointer_loop() {
+        let mut file = tiff(0);
+        // Exif pointer back to IFD0
+        file[30..34].copy_from_slice(&8u32.to_le_bytes());
+        assert!(read_tiff_header(&mut Cursor::new(&file)).is_err());
+    }
+
+    #[test]
+    fn test_read_tiff_header_truncated() {
+        let file = tiff(0);
+        assert!(read_tiff_header(&mut Cursor::new(&file[..50])).is_err());
+    }
+
+    #[test]
+    fn test_detect() {
+        assert!(matches!(
+            detect(b"FUJIFILMCCD-RAW 0201"),
+            Some(Container::Raf)
+        ));
+        assert!(matches!(
+            detect(b"IIRO\x08\0\0\0"),
+            Some(Container::PatchedTiff)
+        ));
+        assert!(matches!(
+            detect(b"IIU\0\x08\0\0\0"),
+            Some(Container::PatchedTiff)
+        ));
+        assert!(matches!(
+            detect(b"\0\0\0\x18ftypcrx \0\0\0\x01"),
+            Some(Container::Cr3)
+        ));
+        assert!(matches!(detect(b"II*\0\x08\0\0\0"), Some(Container::Tiff)));
+        assert!(matches!(detect(b"MM\0*"), Some(Container::Tiff)));
+        assert!(matches!(detect(b"\xff\xd8\xff\xe0"), Some(Container::Jpeg)));
+        assert!(detect(b"\x89PNG\r\n\x1a\n").is_none());
+        assert!(detect(b"\0\0\0\x18ftypheic").is_none());
+        assert!(detect(b"").is_none());
+    }
+
+    #[test]
+    fn test_raf_embedded_jpeg() {
+        let mut raf = vec![0u8; 100];
+        raf[..15].copy_from_slice(b"FUJIFILMCCD-RAW");
+        raf[84..88].copy_from_slice(&100u32.to_be_bytes());
+        raf.extend(jpeg_with_exif(b"MM\0*tiff"));
+
+        let mut reader = Cursor::new(raf);
+        let offset = raf_jpeg_offset(&mut reader).unwrap();
+        assert_eq!(offset, 100);
+        assert_eq!(read_jpeg_exif(&mut reader, offset).unwrap(), b"MM\0*tiff");
+    }
+
+    #[test]
+    fn test_raf_truncated() {
+        let raf = vec![0u8; 86];
+        assert!(raf_jpeg_offset(&mut Cursor::new(raf)).is_err());
+    }
+}
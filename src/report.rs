@@ -0,0 +1,166 @@
+use crate::types::SkippedFile;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Skipped files and their reason, written as they are found: JSON for a `.json` path,
+/// CSV otherwise
+pub struct SkipReport<W: Write> {
+    writer: W,
+    is_json: bool,
+    rows: usize,
+}
+
+impl SkipReport<BufWriter<File>> {
+    pub fn create(path: &Path, file: File) -> io::Result<Self> {
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        Self::new(BufWriter::new(file), is_json)
+    }
+}
+
+impl<W: Write> SkipReport<W> {
+    fn new(mut writer: W, is_json: bool) -> io::Result<Self> {
+        if is_json {
+            writeln!(writer, "[")?;
+        } else {
+            writeln!(writer, "path,reason,detail")?;
+        }
+        Ok(Self {
+            writer,
+            is_json,
+            rows: 0,
+        })
+    }
+
+    pub fn write(&mut self, skipped: &[SkippedFile]) -> io::Result<()> {
+        for file in skipped {
+            if self.is_json {
+                // The separator goes before a row, as the last row takes none
+                if self.rows > 0 {
+                    writeln!(self.writer, ",")?;
+                }
+                write!(
+                    self.writer,
+                    "  {{\"path\": {}, \"reason\": \"{}\", \"detail\": {}}}",
+                    json_string(&file.path.to_string_lossy()),
+                    file.reason.code(),
+                    json_string(&file.detail)
+                )?;
+            } else {
+                writeln!(
+                    self.writer,
+                    "{},{},{}",
+                    csv_field(&file.path.to_string_lossy()),
+                    file.reason.code(),
+                    csv_field(&file.detail)
+                )?;
+            }
+            self.rows += 1;
+        }
+        Ok(())
+    }
+
+    /// Closes the JSON array and flushes, returning the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.is_json {
+            if self.rows > 0 {
+                writeln!(self.writer)?;
+            }
+            writeln!(self.writer, "]")?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Quotes a CSV field when it holds a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SkipReason;
+    use std::path::PathBuf;
+
+    fn skipped() -> Vec<SkippedFile> {
+        vec![
+            SkippedFile {
+                path: PathBuf::from("/photos/no exif.jpg"),
+                reason: SkipReason::NoDate,
+                detail: "No DateTime field in EXIF data".to_string(),
+            },
+            SkippedFile {
+                path: PathBuf::from("/photos/a,\"b\".xmp"),
+                reason: SkipReason::OrphanSidecar,
+                detail: "Sidecar file without its photo".to_string(),
+            },
+        ]
+    }
+
+    fn report(is_json: bool, batches: &[&[SkippedFile]]) -> String {
+        let mut report = SkipReport::new(Vec::new(), is_json).unwrap();
+        for batch in batches {
+            report.write(batch).unwrap();
+        }
+        String::from_utf8(report.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_csv() {
+        let skipped = skipped();
+        assert_eq!(
+            report(false, &[&skipped]),
+            "path,reason,detail\n\
+             /photos/no exif.jpg,no_date,No DateTime field in EXIF data\n\
+             \"/photos/a,\"\"b\"\".xmp\",orphan_sidecar,Sidecar file without its photo\n"
+        );
+    }
+
+    #[test]
+    fn test_json_across_batches() {
+        let skipped = skipped();
+        assert_eq!(
+            report(true, &[&skipped[..1], &[], &skipped[1..]]),
+            "[\n  \
+             {\"path\": \"/photos/no exif.jpg\", \"reason\": \"no_date\", \"detail\": \"No DateTime field in EXIF data\"},\n  \
+             {\"path\": \"/photos/a,\\\"b\\\".xmp\", \"reason\": \"orphan_sidecar\", \"detail\": \"Sidecar file without its photo\"}\n\
+             ]\n"
+        );
+    }
+
+    #[test]
+    fn test_json_empty() {
+        assert_eq!(report(true, &[]), "[\n]\n");
+    }
+
+    #[test]
+    fn test_json_string_escapes() {
+        assert_eq!(json_string("a\\b\n\t\u{1}"), "\"a\\\\b\\n\\t\\u0001\"");
+    }
+}
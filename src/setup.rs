@@ -2,7 +2,8 @@ use crate::types::Args;
 use env_logger::{Builder, Target};
 use log::LevelFilter::{Debug, Info};
 use std::fs::{self, File, OpenOptions};
-use std::path::Path;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
 
 pub fn init_logger(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
     let level = if args.verbose || args.dry_run {
@@ -33,8 +34,10 @@ pub fn need_progress_bar(args: &Args) -> bool {
 }
 
 pub fn validate_io_dirs(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
-    if !args.source.exists() {
-        return Err(format!("Source directory does not exist: {:?}", args.source).into());
+    for source in &args.sources {
+        if !source.exists() {
+            return Err(format!("Source directory does not exist: {:?}", source).into());
+        }
     }
 
     if args.library.exists() && !is_dir_writable(&args.library) {
@@ -45,9 +48,77 @@ pub fn validate_io_dirs(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
         .into());
     }
 
+    // A library inside the source is left out of discovery, but a source inside the library
+    // may receive organized files while it is being walked
+    let library = resolve(&args.library)?;
+    let mut resolved: Vec<(&PathBuf, PathBuf)> = Vec::new();
+    for source in &args.sources {
+        let path = resolve(source)?;
+        if path != library && path.starts_with(&library) {
+            return Err(format!(
+                "Source directory {:?} is inside the library {:?}: organized files could land in the source and be read again. Use the library itself as the source to reorganize it",
+                source, args.library
+            )
+            .into());
+        }
+
+        // Files of a folder given twice, or of a source nested in another, would be read
+        // twice and organized as duplicates of themselves
+        if let Some((other, _)) = resolved
+            .iter()
+            .find(|(_, other)| path.starts_with(other) || other.starts_with(&path))
+        {
+            return Err(format!(
+                "Source directories {:?} and {:?} overlap: give each folder only once",
+                other, source
+            )
+            .into());
+        }
+        resolved.push((source, path));
+    }
+
     Ok(())
 }
 
+/// Whether a source is the library itself, reorganized in place
+pub fn reorganizes_library(args: &Args) -> bool {
+    args.sources.iter().any(|source| is_library(source, args))
+}
+
+/// Whether the source is the library itself, however either path is written
+pub fn is_library(source: &Path, args: &Args) -> bool {
+    match (resolve(source), resolve(&args.library)) {
+        (Ok(source), Ok(library)) => source == library,
+        _ => false,
+    }
+}
+
+/// The library as a path below the source when it is nested inside it, so discovery can
+/// skip it; `None` when the two are apart or the same folder
+pub fn nested_library(source: &Path, args: &Args) -> Option<PathBuf> {
+    let resolved = resolve(source).ok()?;
+    let library = resolve(&args.library).ok()?;
+    let relative = library.strip_prefix(&resolved).ok()?;
+    (!relative.as_os_str().is_empty()).then(|| source.join(relative))
+}
+
+/// Absolute path with symlinks resolved, also for a library that does not exist yet
+pub fn resolve(path: &Path) -> io::Result<PathBuf> {
+    match path.canonicalize() {
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            let Some(name) = path.file_name() else {
+                return Err(err);
+            };
+            let parent = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            Ok(resolve(parent)?.join(name))
+        }
+        resolved => resolved,
+    }
+}
+
 fn is_dir_writable(path: &Path) -> bool {
     let test_file = path.join(".writability_test");
 
@@ -0,0 +1,255 @@
+use crate::types::{MEDIA_EXTENSIONS, PhotoDateTime};
+use chrono::{DateTime, Datelike, Local};
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Takeout cuts JSON file names to this many characters before `.json`
+const MAX_JSON_BASE_LEN: usize = 46;
+
+/// Newer exports name the JSON `photo.jpg.supplemental-metadata.json`
+const SUPPLEMENTAL_SUFFIX: &str = ".supplemental-metadata";
+
+/// Suffix Google Photos gives to the edited copy, which shares the original's JSON
+const EDITED_SUFFIX: &str = "-edited";
+
+static PHOTO_TAKEN_TIMESTAMP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#""photoTakenTime"\s*:\s*\{[^}]*?"timestamp"\s*:\s*"?(\d+)"?"#)
+        .expect("photoTakenTime pattern should be valid")
+});
+
+static DUPLICATE_COUNTER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(.*)(\(\d+\))(\.[^.]*)?$").expect("counter pattern should be valid")
+});
+
+/// Whether a file is named like the Takeout JSON of a media file, as listed by
+/// [`json_names`]; other JSON, such as album `metadata.json`, is not
+pub fn is_takeout_json(path: &Path) -> bool {
+    if !path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+    {
+        return false;
+    }
+    let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+        return false;
+    };
+
+    let base = stem
+        .strip_suffix(')')
+        .and_then(|stem| stem.rsplit_once('('))
+        .filter(|(_, counter)| !counter.is_empty() && counter.bytes().all(|b| b.is_ascii_digit()))
+        .map_or(stem, |(base, _)| base);
+    // A name cut short may have lost the media extension
+    if base.chars().count() == MAX_JSON_BASE_LEN {
+        return true;
+    }
+    let media = base.strip_suffix(SUPPLEMENTAL_SUFFIX).unwrap_or(base);
+    Path::new(media)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| ext != "json" && MEDIA_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Reads `photoTakenTime` from the Google Takeout JSON of a media file,
+/// converted from UTC to the system time zone
+pub fn extract_takeout_datetime(path: &Path) -> Option<PhotoDateTime> {
+    if is_takeout_json(path) {
+        return None;
+    }
+
+    let dir = path.parent()?;
+    json_names(path)
+        .iter()
+        .filter_map(|name| fs::read_to_string(dir.join(name)).ok())
+        .find_map(|json| datetime_from_json(&json))
+}
+
+/// Names the Takeout JSON of a media file may have, most specific first:
+/// `photo.jpg.json`, the supplemental-metadata form, names cut at 46 characters,
+/// the `(1)` counter moved behind the extension and the original's JSON for `-edited` copies
+pub(crate) fn json_names(path: &Path) -> Vec<String> {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return vec![];
+    };
+
+    // (base name, counter placed before `.json`)
+    let mut bases = vec![(name.to_string(), String::new())];
+    if let Some(captures) = DUPLICATE_COUNTER.captures(name) {
+        let ext = captures.get(3).map_or("", |ext| ext.as_str());
+        bases.push((format!("{}{}", &captures[1], ext), captures[2].to_string()));
+    }
+    for index in 0..bases.len() {
+        let (base, counter) = bases[index].clone();
+        if let Some((stem, ext)) = base.rsplit_once('.')
+            && let Some(original) = stem.strip_suffix(EDITED_SUFFIX)
+        {
+            bases.push((format!("{}.{}", original, ext), counter));
+        }
+    }
+
+    let mut names = Vec::new();
+    for (base, counter) in bases {
+        for suffix in ["", SUPPLEMENTAL_SUFFIX] {
+            let full: String = format!("{}{}", base, suffix)
+                .chars()
+                .take(MAX_JSON_BASE_LEN)
+                .collect();
+            let name = format!("{}{}.json", full, counter);
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+fn datetime_from_json(json: &str) -> Option<PhotoDateTime> {
+    let seconds: i64 = PHOTO_TAKEN_TIMESTAMP.captures(json)?[1].parse().ok()?;
+    // Zero means Takeout did not know the capture time
+    if seconds == 0 {
+        return None;
+    }
+
+    let dt = DateTime::from_timestamp(seconds, 0)?
+        .with_timezone(&Local)
+        .fixed_offset();
+    (dt.year() >= 1970).then(|| dt.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod is_takeout_json {
+        use super::*;
+
+        #[test]
+        fn test_names_of_media_files() {
+            for name in [
+                "IMG_1234.jpg.json",
+                "IMG_1234.JPG.JSON",
+                "IMG_1234.jpg.supplemental-metadata.json",
+                "IMG_1234.jpg(1).json",
+                "IMG_1234.jpg.supplemental-metadata(1).json",
+                "Screenshot_20190913-201547_Google Photos Backu.json",
+                "PXL_20230514_112233444.NIGHT.jpg.supplemental-.json",
+            ] {
+                assert!(is_takeout_json(Path::new(name)), "{}", name);
+            }
+        }
+
+        #[test]
+        fn test_other_json() {
+            for name in [
+                "metadata.json",
+                "print-subscriptions.json",
+                "user-generated-memory-titles(1).json",
+                "settings.backup.json",
+                "IMG_1234.jpg",
+            ] {
+                assert!(!is_takeout_json(Path::new(name)), "{}", name);
+            }
+        }
+    }
+
+    mod json_names {
+        use super::*;
+
+        #[test]
+        fn test_plain() {
+            assert_eq!(
+                json_names(Path::new("Takeout/IMG_1234.jpg")),
+                vec![
+                    "IMG_1234.jpg.json",
+                    "IMG_1234.jpg.supplemental-metadata.json"
+                ]
+            );
+        }
+
+        #[test]
+        fn test_duplicate_counter() {
+            assert_eq!(
+                json_names(Path::new("IMG_1234(1).jpg")),
+                vec![
+                    "IMG_1234(1).jpg.json",
+                    "IMG_1234(1).jpg.supplemental-metadata.json",
+                    "IMG_1234.jpg(1).json",
+                    "IMG_1234.jpg.supplemental-metadata(1).json"
+                ]
+            );
+        }
+
+        #[test]
+        fn test_truncated() {
+            let names = json_names(Path::new(
+                "Screenshot_20190913-201547_Google Photos Backup.jpg",
+            ));
+            assert_eq!(
+                names[0],
+                "Screenshot_20190913-201547_Google Photos Backu.json"
+            );
+            assert_eq!(names.len(), 1);
+
+            assert_eq!(
+                json_names(Path::new("PXL_20230514_112233444.NIGHT.jpg"))[1],
+                "PXL_20230514_112233444.NIGHT.jpg.supplemental-.json"
+            );
+        }
+
+        #[test]
+        fn test_edited() {
+            assert_eq!(
+                json_names(Path::new("IMG_1234-edited.jpg")),
+                vec![
+                    "IMG_1234-edited.jpg.json",
+                    "IMG_1234-edited.jpg.supplemental-metadata.json",
+                    "IMG_1234.jpg.json",
+                    "IMG_1234.jpg.supplemental-metadata.json"
+                ]
+            );
+        }
+    }
+
+    mod datetime_from_json {
+        use super::*;
+
+        #[test]
+        fn test_photo_taken_time() {
+            let json = r#"{
+                "title": "IMG_1234.jpg",
+                "creationTime": { "timestamp": "1700000000", "formatted": "..." },
+                "photoTakenTime": {
+                    "timestamp": "1561717800",
+                    "formatted": "28 Jun 2019, 10:30:00 UTC"
+                }
+            }"#;
+            let expected: PhotoDateTime = DateTime::from_timestamp(1_561_717_800, 0)
+                .unwrap()
+                .with_timezone(&Local)
+                .fixed_offset()
+                .into();
+            let dt = datetime_from_json(json).unwrap();
+            assert_eq!(
+                (dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.offset),
+                (
+                    expected.year,
+                    expected.month,
+                    expected.day,
+                    expected.hour,
+                    expected.minute,
+                    expected.offset
+                )
+            );
+        }
+
+        #[test]
+        fn test_missing_or_unset() {
+            assert!(
+                datetime_from_json(r#"{"creationTime": {"timestamp": "1700000000"}}"#).is_none()
+            );
+            assert!(datetime_from_json(r#"{"photoTakenTime": {"timestamp": "0"}}"#).is_none());
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use indicatif::ProgressBar;
-use log::{debug, error, info};
+use log::{debug, error};
 use std::fs;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 
+use crate::discovery::Placed;
+use crate::organizer::PathGroup;
 use crate::types::Action;
 
 enum TransferOutcome {
@@ -13,30 +15,39 @@ enum TransferOutcome {
 use TransferOutcome::*;
 
 pub fn transfer_multiple(
-    path_pairs: Vec<(PathBuf, PathBuf)>,
+    path_groups: Vec<PathGroup>,
     dry_run: bool,
     action: Action,
     progress_bar: &Option<ProgressBar>,
+    placed: Option<&Placed>,
 ) -> (usize, usize, usize) {
-    info!("Will organize {} photos", path_pairs.len());
+    debug!("Will organize {} photos", path_groups.len());
 
     let mut transferred = 0;
     let mut already_organized = 0;
     let mut failed = 0;
 
     if dry_run {
-        for (src, dst) in path_pairs.iter() {
+        for (src, dst, companions) in path_groups.iter() {
             match (src.canonicalize(), dst.canonicalize()) {
-                (Ok(s), Ok(d)) if d == s => already_organized += 1,
+                (Ok(s), Ok(d)) if d == s => already_organized += 1 + companions.len(),
                 _ => {
                     debug!("Would transfer from {} to {}", src.display(), dst.display());
                     transferred += 1;
+                    for companion in companions {
+                        debug!(
+                            "Would transfer from {} to {}",
+                            companion.display(),
+                            companion_destination(src, dst, companion).display()
+                        );
+                        transferred += 1;
+                    }
                 }
             }
         }
     } else {
-        for (src, dst) in path_pairs.iter() {
-            match transfer_one(src, dst, action) {
+        for (src, dst, companions) in path_groups.iter() {
+            match transfer_one(src, dst, companions, action, placed) {
                 Ok(Transferred(pb)) => {
                     debug!(
                         "Successfully organized file from {} to {}",
@@ -44,14 +55,35 @@ pub fn transfer_multiple(
                         pb.display()
                     );
                     transferred += 1;
+
+                    for companion in companions {
+                        let companion_dst = companion_destination(src, &pb, companion);
+                        if let Some(placed) = placed {
+                            placed.record(&companion_dst);
+                        }
+                        match move_or_copy(companion, companion_dst, action) {
+                            Ok(companion_pb) => {
+                                debug!(
+                                    "Successfully organized file from {} to {}",
+                                    companion.display(),
+                                    companion_pb.display()
+                                );
+                                transferred += 1;
+                            }
+                            Err(err) => {
+                                error!("Failed to organize file {}: {}", companion.display(), err);
+                                failed += 1;
+                            }
+                        }
+                    }
                 }
                 Ok(AlreadyInPlace(pb)) => {
                     debug!("Already organized file {}", pb.display());
-                    already_organized += 1;
+                    already_organized += 1 + companions.len();
                 }
                 Err(err) => {
                     error!("Failed to organize file {}: {}", src.display(), err);
-                    failed += 1;
+                    failed += 1 + companions.len();
                 }
             }
             if let Some(pb) = progress_bar {
@@ -64,8 +96,10 @@ pub fn transfer_multiple(
 
 fn transfer_one(
     source: &PathBuf,
-    destination: &PathBuf,
+    destination: &Path,
+    companions: &[PathBuf],
     action: Action,
+    placed: Option<&Placed>,
 ) -> Result<TransferOutcome, std::io::Error> {
     match (source.canonicalize(), destination.canonicalize()) {
         (Ok(s), Ok(d)) if d == s => Ok(AlreadyInPlace(destination.to_path_buf())),
@@ -73,26 +107,85 @@ fn transfer_one(
             let parent_dir = destination
                 .parent()
                 .expect("destination should have parent directory");
-            fs::create_dir_all(parent_dir)?;
 
-            let final_destination = if fs::exists(destination)? {
-                next_available_name(destination, parent_dir, |p| fs::exists(p))?
+            // A name is free only when every companion's name derived from it is free too
+            let is_taken = |path: &Path| -> Result<bool, std::io::Error> {
+                if fs::exists(path)? {
+                    return Ok(true);
+                }
+                for companion in companions {
+                    if fs::exists(companion_destination(source, path, companion))? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            };
+
+            let final_destination = if is_taken(destination)? {
+                next_available_name(destination, parent_dir, is_taken)?
             } else {
                 destination.to_path_buf()
             };
 
-            match action {
-                Action::Move => Ok(Transferred(rename(source, final_destination)?)),
-                Action::Copy => {
-                    fs::copy(source, &final_destination)?;
-                    Ok(Transferred(final_destination))
-                }
+            // Recorded before the folder exists, so the walk can tell it was created here
+            if let Some(placed) = placed {
+                placed.record(&final_destination);
             }
+            fs::create_dir_all(parent_dir)?;
+            Ok(Transferred(move_or_copy(
+                source,
+                final_destination,
+                action,
+            )?))
+        }
+    }
+}
+
+/// Destination of a companion file, following the final name of its primary:
+/// whatever follows the primary's stem is kept (`photo.jpg.xmp` becomes `<stem>.jpg.xmp`),
+/// otherwise only the companion's extension is
+fn companion_destination(source: &Path, destination: &Path, companion: &Path) -> PathBuf {
+    let source_stem = source.file_stem().unwrap_or_default().to_string_lossy();
+    let companion_name = companion.file_name().unwrap_or_default().to_string_lossy();
+    let destination_stem = destination
+        .file_stem()
+        .expect("destination should have a file name")
+        .to_string_lossy();
+
+    match companion_name.strip_prefix(source_stem.as_ref()) {
+        Some(suffix) if suffix.starts_with('.') => {
+            destination.with_file_name(format!("{}{}", destination_stem, suffix))
+        }
+        _ => match companion.extension() {
+            Some(ext) => destination.with_extension(ext),
+            None => destination.with_extension(""),
+        },
+    }
+}
+
+fn move_or_copy(
+    source: &PathBuf,
+    destination: PathBuf,
+    action: Action,
+) -> Result<PathBuf, std::io::Error> {
+    match action {
+        Action::Move => rename(source, destination),
+        Action::Copy => {
+            fs::copy(source, &destination)?;
+            Ok(destination)
         }
     }
 }
 
 fn rename(source: &PathBuf, destination: PathBuf) -> Result<PathBuf, std::io::Error> {
+    // Renaming a followed symlink would move the link and leave the photo behind: the
+    // photo is copied into the library and only the link is removed
+    if fs::symlink_metadata(source)?.file_type().is_symlink() {
+        fs::copy(source, &destination)?;
+        fs::remove_file(source)?;
+        return Ok(destination);
+    }
+
     match fs::rename(source, &destination) {
         Ok(_) => Ok(destination),
         Err(e) if e.kind() == ErrorKind::CrossesDevices => {
@@ -140,6 +233,54 @@ where
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_companion_destination_replaces_extension() {
+        assert_eq!(
+            companion_destination(
+                Path::new("card/DSC_0001.NEF"),
+                Path::new("lib/2025/20250301_101500(1).NEF"),
+                Path::new("card/DSC_0001.JPG")
+            ),
+            PathBuf::from("lib/2025/20250301_101500(1).JPG")
+        );
+    }
+
+    #[test]
+    fn test_companion_destination_appended_extension() {
+        assert_eq!(
+            companion_destination(
+                Path::new("card/photo.jpg"),
+                Path::new("lib/20250301_101500.jpg"),
+                Path::new("card/photo.jpg.xmp")
+            ),
+            PathBuf::from("lib/20250301_101500.jpg.xmp")
+        );
+    }
+
+    #[test]
+    fn test_companion_destination_sidecar_of_companion() {
+        assert_eq!(
+            companion_destination(
+                Path::new("card/DSC_0001.NEF"),
+                Path::new("lib/20250301_101500(2).NEF"),
+                Path::new("card/DSC_0001.JPG.xmp")
+            ),
+            PathBuf::from("lib/20250301_101500(2).JPG.xmp")
+        );
+    }
+
+    #[test]
+    fn test_companion_destination_different_stem() {
+        assert_eq!(
+            companion_destination(
+                Path::new("card/IMG_0001.HEIC"),
+                Path::new("lib/IMG_0001.HEIC"),
+                Path::new("card/IMG_E0001.AAE")
+            ),
+            PathBuf::from("lib/IMG_0001.AAE")
+        );
+    }
+
     #[test]
     fn test_next_available_name_no_conflicts() {
         let file_path = Path::new("photos/myfile.jpg");
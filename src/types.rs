@@ -1,4 +1,8 @@
-use std::path::PathBuf;
+use chrono::NaiveDate;
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 #[derive(Debug, Copy, Clone)]
@@ -47,17 +51,455 @@ impl FromStr for Action {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DateTag {
+    Original,
+    Digitized,
+    Modified,
+}
+
+impl FromStr for DateTag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "original" => Ok(Self::Original),
+            "digitized" => Ok(Self::Digitized),
+            "modified" => Ok(Self::Modified),
+            _ => Err(format!(
+                "Error: '{}' is not a valid date tag. Valid date tags: original, digitized or modified",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenameFormat {
+    /// YYYYMMDD_hhmmss
+    Seconds,
+    /// YYYYMMDD_hhmmss_SSS
+    Milliseconds,
+}
+
+impl FromStr for RenameFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "seconds" => Ok(Self::Seconds),
+            "milliseconds" => Ok(Self::Milliseconds),
+            _ => Err(format!(
+                "Error: '{}' is not a valid rename format. Valid rename formats: seconds or milliseconds",
+                s
+            )),
+        }
+    }
+}
+
+/// Time zone used to decide which day a photo belongs to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TimeZone {
+    /// Camera wall-clock time, as recorded
+    Local,
+    /// Fixed offset from UTC in minutes
+    Offset(i16),
+}
+
+impl FromStr for TimeZone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Error: '{}' is not a valid time zone. Valid time zones: local, utc or an offset like +02:00",
+                s
+            )
+        };
+
+        match s {
+            "local" => return Ok(Self::Local),
+            "utc" => return Ok(Self::Offset(0)),
+            _ => (),
+        }
+
+        let sign = match s.as_bytes().first() {
+            Some(b'+') => 1,
+            Some(b'-') => -1,
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = s[1..].split_once(':').ok_or_else(invalid)?;
+        let is_two_digits =
+            |part: &str| part.len() == 2 && part.bytes().all(|b| b.is_ascii_digit());
+        if !is_two_digits(hours) || !is_two_digits(minutes) {
+            return Err(invalid());
+        }
+        let hours: i16 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i16 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 14 || minutes > 59 {
+            return Err(invalid());
+        }
+
+        Ok(Self::Offset(sign * (hours * 60 + minutes)))
+    }
+}
+
+/// When XMP sidecars and embedded XMP packets are consulted for the capture date
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum XmpMode {
+    Ignore,
+    /// Only when the file has no native date
+    Fallback,
+    /// Before the native date, e.g. for files edited in darktable or Lightroom
+    Prefer,
+}
+
+impl FromStr for XmpMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(Self::Ignore),
+            "fallback" => Ok(Self::Fallback),
+            "prefer" => Ok(Self::Prefer),
+            _ => Err(format!(
+                "Error: '{}' is not a valid XMP mode. Valid XMP modes: ignore, fallback or prefer",
+                s
+            )),
+        }
+    }
+}
+
+/// Filesystem timestamp used as a last-resort capture date
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FileTime {
+    Modified,
+    /// Birth time where the platform records it, modification time otherwise
+    Created,
+}
+
+impl FromStr for FileTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "modified" => Ok(Self::Modified),
+            "created" => Ok(Self::Created),
+            _ => Err(format!(
+                "Error: '{}' is not a valid file time. Valid file times: modified or created",
+                s
+            )),
+        }
+    }
+}
+
+/// File name layout carrying a capture date.
+///
+/// The regular expression must capture `year`, `month` and `day`; `hour`, `minute`,
+/// `second` and `millis` are optional and default to zero.
+#[derive(Debug, Clone)]
+pub struct FilenamePattern(Regex);
+
+impl FilenamePattern {
+    /// Naming schemes of common phones and messengers, e.g. `IMG_20250615_143052.jpg`,
+    /// `PXL_20250615_143052123.jpg`, `Screenshot_2025-06-15-14-30-52.png` and
+    /// `IMG-20250615-WA0003.jpg`
+    pub fn defaults() -> Vec<Self> {
+        [
+            r"(?:^|\D)(?P<year>\d{4})(?P<month>\d{2})(?P<day>\d{2})[_-](?P<hour>\d{2})(?P<minute>\d{2})(?P<second>\d{2})(?P<millis>\d{3})?(?:\D|$)",
+            r"(?:^|\D)(?P<year>\d{4})-(?P<month>\d{2})-(?P<day>\d{2})[-_ ](?P<hour>\d{2})[-.](?P<minute>\d{2})[-.](?P<second>\d{2})(?:\D|$)",
+            r"(?:^|\D)(?P<year>\d{4})(?P<month>\d{2})(?P<day>\d{2})-WA\d+",
+        ]
+        .iter()
+        .map(|pattern| pattern.parse().expect("default patterns should be valid"))
+        .collect()
+    }
+
+    pub fn regex(&self) -> &Regex {
+        &self.0
+    }
+}
+
+impl FromStr for FilenamePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = Regex::new(s)
+            .map_err(|e| format!("Error: '{}' is not a valid file name pattern: {}", s, e))?;
+
+        let groups: Vec<&str> = regex.capture_names().flatten().collect();
+        for required in ["year", "month", "day"] {
+            if !groups.contains(&required) {
+                return Err(format!(
+                    "Error: file name pattern '{}' must capture a '{}' group",
+                    s, required
+                ));
+            }
+        }
+
+        Ok(Self(regex))
+    }
+}
+
+/// Extensions read when none are given: photos, RAW, videos, sidecars and Takeout JSON.
+/// Formats without a date the tool can read, such as `gif` or `avi`, are included
+/// for the file name and file time fallbacks.
+pub const MEDIA_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "tif", "tiff", "heic", "heif", "webp", "gif", "avif", "cr2", "cr3",
+    "crw", "nef", "nrw", "arw", "srf", "sr2", "raf", "orf", "rw2", "dng", "pef", "srw", "mp4",
+    "m4v", "mov", "3gp", "3g2", "mts", "m2ts", "avi", "xmp", "aae", "thm", "pp3", "json",
+];
+
+/// Glob matched against paths relative to the source, e.g. `2024-*/**` or `**/cache/*`.
+///
+/// `*` does not cross folders. A pattern without `/` matches file and folder names at any
+/// depth, e.g. `*.tmp` or `Thumbs.db`.
+#[derive(Debug, Clone)]
+pub struct PathPattern {
+    matcher: GlobMatcher,
+    name_only: bool,
+}
+
+impl PathPattern {
+    pub fn is_match(&self, relative: &Path) -> bool {
+        if self.name_only {
+            relative
+                .file_name()
+                .is_some_and(|name| self.matcher.is_match(name))
+        } else {
+            self.matcher.is_match(relative)
+        }
+    }
+}
+
+impl FromStr for PathPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let glob = GlobBuilder::new(s.trim_start_matches('/'))
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("Error: '{}' is not a valid glob pattern: {}", s, e))?;
+
+        Ok(Self {
+            matcher: glob.compile_matcher(),
+            name_only: !s.contains('/'),
+        })
+    }
+}
+
+/// Shift for photos from a camera whose clock was off.
+///
+/// Written as `make,model,serial,offset[,from,until]`, e.g.
+/// `Canon,Canon EOS R6,,+01:12,2025-06-01,2025-06-14`. Empty or `*` camera fields match
+/// any value; the offset (`±HH:MM[:SS]`) is added to the recorded time of photos taken
+/// between `from` and `until` inclusive, judged by their recorded date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockCorrection {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    /// Seconds added to the recorded time
+    pub shift: i64,
+    pub from: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+impl ClockCorrection {
+    /// Parses a correction table, one correction per line; blank lines and `#` comments
+    /// are ignored
+    pub fn parse_table(table: &str) -> Result<Vec<Self>, String> {
+        table
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(number, line)| {
+                line.parse()
+                    .map_err(|e| format!("{} (clock corrections line {})", e, number))
+            })
+            .collect()
+    }
+}
+
+impl FromStr for ClockCorrection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(',').map(str::trim).collect();
+        if !matches!(fields.len(), 4 | 6) {
+            return Err(format!(
+                "Error: '{}' is not a valid clock correction. Expected make,model,serial,offset[,from,until]",
+                s
+            ));
+        }
+
+        let camera_field = |field: &str| match field {
+            "" | "*" => None,
+            field => Some(field.to_string()),
+        };
+        let date = |field: &str| match field {
+            "" => Ok(None),
+            field => parse_date(field).map(Some),
+        };
+
+        let (from, until) = match fields.len() {
+            6 => (date(fields[4])?, date(fields[5])?),
+            _ => (None, None),
+        };
+        if let (Some(from), Some(until)) = (from, until)
+            && from > until
+        {
+            return Err(format!(
+                "Error: clock correction range {} to {} ends before it starts",
+                from, until
+            ));
+        }
+
+        Ok(Self {
+            make: camera_field(fields[0]),
+            model: camera_field(fields[1]),
+            serial: camera_field(fields[2]),
+            shift: parse_clock_offset(fields[3])?,
+            from,
+            until,
+        })
+    }
+}
+
+/// Parses a `YYYY-MM-DD` calendar date
+pub fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| {
+        format!(
+            "Error: '{}' is not a valid date. Valid dates look like 2025-06-01",
+            s
+        )
+    })
+}
+
+/// Parses `±HH:MM[:SS]` into seconds
+fn parse_clock_offset(s: &str) -> Result<i64, String> {
+    let invalid = || {
+        format!(
+            "Error: '{}' is not a valid clock offset. Valid offsets look like +01:12 or -00:00:45",
+            s
+        )
+    };
+
+    let sign = match s.as_bytes().first() {
+        Some(b'+') => 1,
+        Some(b'-') => -1,
+        _ => return Err(invalid()),
+    };
+    let parts: Vec<&str> = s[1..].split(':').collect();
+    if !matches!(parts.len(), 2 | 3)
+        || parts
+            .iter()
+            .any(|part| part.len() != 2 || !part.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return Err(invalid());
+    }
+
+    let numbers: Vec<i64> = parts
+        .iter()
+        .map(|part| part.parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    if numbers[1..].iter().any(|&n| n > 59) {
+        return Err(invalid());
+    }
+
+    let seconds = numbers[0] * 3600 + numbers[1] * 60 + numbers.get(2).copied().unwrap_or(0);
+    Ok(sign * seconds)
+}
+
 #[derive(Clone)]
 pub struct Args {
-    pub source: PathBuf,
+    pub sources: Vec<PathBuf>,
     pub library: PathBuf,
     pub mode: Mode,
     pub limit: u16,
     pub rename: bool,
+    pub rename_format: RenameFormat,
     pub action: Action,
     pub dry_run: bool,
     pub log_file: Option<PathBuf>,
     pub verbose: bool,
+    pub date_tags: Vec<DateTag>,
+    pub timezone: TimeZone,
+    pub xmp: XmpMode,
+    /// File name patterns to date files without EXIF; empty disables the fallback
+    pub filename_patterns: Vec<FilenamePattern>,
+    /// Filesystem timestamp to date whatever is left; these files go to an `_mtime` subtree
+    pub file_time: Option<FileTime>,
+    /// Per-camera clock fixes; the first matching correction is applied
+    pub clock_corrections: Vec<ClockCorrection>,
+    /// Threads reading metadata; 0 uses one per CPU core
+    pub threads: usize,
+    /// Where to list skipped files: JSON for a `.json` extension, CSV otherwise
+    pub skip_report: Option<PathBuf>,
+    /// Transfer files that could not be dated to `_undated` instead of leaving them behind
+    pub quarantine: bool,
+    /// First capture date to organize, inclusive
+    pub since: Option<NaiveDate>,
+    /// Last capture date to organize, inclusive
+    pub until: Option<NaiveDate>,
+    /// Lowercase extensions to read; `None` reads [`MEDIA_EXTENSIONS`] and an empty list
+    /// every file. Files without an extension, other than dot files, are always read as
+    /// their EXIF may still hold a date
+    pub extensions: Option<Vec<String>>,
+    /// Lowercase extensions never read
+    pub exclude_extensions: Vec<String>,
+    /// When not empty, only files matching one of these are read
+    pub include: Vec<PathPattern>,
+    /// Files and folders left out of discovery
+    pub exclude: Vec<PathPattern>,
+    /// Also walk NAS and OS metadata folders such as `@eaDir` and `.Trashes`
+    pub include_system_dirs: bool,
+    /// Walk into symlinked folders and read symlinked files; links back to a folder being
+    /// walked are reported and skipped
+    pub follow_symlinks: bool,
+    /// Leave out files and folders whose name starts with a dot
+    pub skip_hidden: bool,
+    /// How deep to walk below the source; 1 reads only the files directly in it
+    pub max_depth: Option<usize>,
+}
+
+impl Args {
+    /// Organizes `sources` into `library` with the command line defaults: daily folders,
+    /// files moved under their own names and dated from EXIF and XMP only
+    pub fn new(sources: Vec<PathBuf>, library: PathBuf) -> Self {
+        Self {
+            sources,
+            library,
+            mode: Mode::Daily,
+            limit: 25,
+            rename: false,
+            rename_format: RenameFormat::Seconds,
+            action: Action::Move,
+            dry_run: false,
+            log_file: None,
+            verbose: false,
+            date_tags: vec![DateTag::Original, DateTag::Digitized, DateTag::Modified],
+            timezone: TimeZone::Local,
+            xmp: XmpMode::Fallback,
+            filename_patterns: vec![],
+            file_time: None,
+            clock_corrections: vec![],
+            threads: 0,
+            skip_report: None,
+            quarantine: false,
+            since: None,
+            until: None,
+            extensions: None,
+            exclude_extensions: vec![],
+            include: vec![],
+            exclude: vec![],
+            include_system_dirs: false,
+            follow_symlinks: false,
+            skip_hidden: false,
+            max_depth: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -68,11 +510,93 @@ pub struct PhotoDateTime {
     pub hour: u8,
     pub minute: u8,
     pub second: u8,
+    /// Offset from UTC in minutes, when the camera recorded one
+    pub offset: Option<i16>,
+    /// Sub-second part of the capture time, when the camera recorded one
+    pub nanosecond: Option<u32>,
+}
+
+/// Where the capture date of a photo was taken from
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DateSource {
+    Exif,
+    /// Creation time from MP4/MOV/3GP QuickTime atoms
+    Video,
+    /// XMP sidecar or embedded XMP packet
+    Xmp,
+    /// `photoTakenTime` from a Google Takeout JSON
+    Takeout,
+    Filename,
+    /// Filesystem timestamp, which copies and syncs may have reset
+    FileTime,
+}
+
+impl fmt::Display for DateSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Exif => write!(f, "EXIF"),
+            Self::Video => write!(f, "video metadata"),
+            Self::Xmp => write!(f, "XMP"),
+            Self::Takeout => write!(f, "Google Takeout"),
+            Self::Filename => write!(f, "file name"),
+            Self::FileTime => write!(f, "file time"),
+        }
+    }
+}
+
+/// Why a file was left in the source
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SkipReason {
+    /// No date in the metadata nor from any enabled fallback
+    NoDate,
+    /// A date tag was present but does not hold a valid date
+    InvalidDate,
+    /// Corrupt or unsupported metadata
+    Unreadable,
+    /// The file could not be opened or read
+    Io,
+    /// Sidecar or Takeout JSON without its photo
+    OrphanSidecar,
+}
+
+impl SkipReason {
+    /// Stable identifier used in skip reports
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NoDate => "no_date",
+            Self::InvalidDate => "invalid_date",
+            Self::Unreadable => "unreadable",
+            Self::Io => "io_error",
+            Self::OrphanSidecar => "orphan_sidecar",
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoDate => write!(f, "no date"),
+            Self::InvalidDate => write!(f, "invalid date"),
+            Self::Unreadable => write!(f, "unreadable metadata"),
+            Self::Io => write!(f, "I/O error"),
+            Self::OrphanSidecar => write!(f, "orphan sidecar"),
+        }
+    }
+}
+
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+    /// The underlying error message
+    pub detail: String,
 }
 
 pub struct PhotoMetadata {
     pub path: PathBuf,
     pub datetime: PhotoDateTime,
+    pub source: DateSource,
+    /// Files that share the destination name of `path`, e.g. the JPEG of a RAW+JPEG pair
+    pub companions: Vec<PathBuf>,
 }
 
 #[cfg(test)]
@@ -162,4 +686,336 @@ mod tests {
             assert!(result.is_err());
         }
     }
+
+    mod date_tag_from_str {
+        use super::*;
+
+        #[test]
+        fn test_valid_original() {
+            assert!(matches!(
+                DateTag::from_str("original"),
+                Ok(DateTag::Original)
+            ));
+        }
+
+        #[test]
+        fn test_valid_digitized() {
+            assert!(matches!(
+                DateTag::from_str("digitized"),
+                Ok(DateTag::Digitized)
+            ));
+        }
+
+        #[test]
+        fn test_valid_modified() {
+            assert!(matches!(
+                DateTag::from_str("modified"),
+                Ok(DateTag::Modified)
+            ));
+        }
+
+        #[test]
+        fn test_invalid_value() {
+            let result = DateTag::from_str("created");
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err(),
+                "Error: 'created' is not a valid date tag. Valid date tags: original, digitized or modified"
+            );
+        }
+    }
+
+    mod rename_format_from_str {
+        use super::*;
+
+        #[test]
+        fn test_valid_seconds() {
+            assert_eq!(RenameFormat::from_str("seconds"), Ok(RenameFormat::Seconds));
+        }
+
+        #[test]
+        fn test_valid_milliseconds() {
+            assert_eq!(
+                RenameFormat::from_str("milliseconds"),
+                Ok(RenameFormat::Milliseconds)
+            );
+        }
+
+        #[test]
+        fn test_invalid_value() {
+            let result = RenameFormat::from_str("ms");
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err(),
+                "Error: 'ms' is not a valid rename format. Valid rename formats: seconds or milliseconds"
+            );
+        }
+    }
+
+    mod xmp_mode_from_str {
+        use super::*;
+
+        #[test]
+        fn test_valid_values() {
+            assert_eq!(XmpMode::from_str("ignore"), Ok(XmpMode::Ignore));
+            assert_eq!(XmpMode::from_str("fallback"), Ok(XmpMode::Fallback));
+            assert_eq!(XmpMode::from_str("prefer"), Ok(XmpMode::Prefer));
+        }
+
+        #[test]
+        fn test_invalid_value() {
+            let result = XmpMode::from_str("always");
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err(),
+                "Error: 'always' is not a valid XMP mode. Valid XMP modes: ignore, fallback or prefer"
+            );
+        }
+    }
+
+    mod file_time_from_str {
+        use super::*;
+
+        #[test]
+        fn test_valid_modified() {
+            assert_eq!(FileTime::from_str("modified"), Ok(FileTime::Modified));
+        }
+
+        #[test]
+        fn test_valid_created() {
+            assert_eq!(FileTime::from_str("created"), Ok(FileTime::Created));
+        }
+
+        #[test]
+        fn test_invalid_value() {
+            let result = FileTime::from_str("accessed");
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err(),
+                "Error: 'accessed' is not a valid file time. Valid file times: modified or created"
+            );
+        }
+    }
+
+    mod filename_pattern_from_str {
+        use super::*;
+
+        #[test]
+        fn test_valid_pattern() {
+            assert!(
+                FilenamePattern::from_str(r"^DSC(?P<year>\d{4})(?P<month>\d{2})(?P<day>\d{2})")
+                    .is_ok()
+            );
+        }
+
+        #[test]
+        fn test_defaults_are_valid() {
+            assert_eq!(FilenamePattern::defaults().len(), 3);
+        }
+
+        #[test]
+        fn test_invalid_regex() {
+            let result = FilenamePattern::from_str("(?P<year>");
+            assert!(result.is_err());
+            assert!(
+                result
+                    .unwrap_err()
+                    .contains("not a valid file name pattern")
+            );
+        }
+
+        #[test]
+        fn test_missing_group() {
+            let result = FilenamePattern::from_str(r"(?P<year>\d{4})(?P<month>\d{2})");
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err(),
+                r"Error: file name pattern '(?P<year>\d{4})(?P<month>\d{2})' must capture a 'day' group"
+            );
+        }
+    }
+
+    mod path_pattern_from_str {
+        use super::*;
+
+        #[test]
+        fn test_name_only() {
+            let pattern = PathPattern::from_str("*.tmp").unwrap();
+            assert!(pattern.is_match(Path::new("a.tmp")));
+            assert!(pattern.is_match(Path::new("2024/trip/a.tmp")));
+            assert!(!pattern.is_match(Path::new("a.tmp.jpg")));
+        }
+
+        #[test]
+        fn test_relative_path() {
+            let pattern = PathPattern::from_str("2024-*/**").unwrap();
+            assert!(pattern.is_match(Path::new("2024-06 Rome/IMG_1.JPG")));
+            assert!(pattern.is_match(Path::new("2024-06 Rome/day 2/IMG_2.JPG")));
+            assert!(!pattern.is_match(Path::new("2023-06/IMG_1.JPG")));
+            assert!(!pattern.is_match(Path::new("old/2024-06/IMG_1.JPG")));
+        }
+
+        #[test]
+        fn test_star_does_not_cross_folders() {
+            let pattern = PathPattern::from_str("/cards/*.jpg").unwrap();
+            assert!(pattern.is_match(Path::new("cards/a.jpg")));
+            assert!(!pattern.is_match(Path::new("cards/b/a.jpg")));
+        }
+
+        #[test]
+        fn test_invalid() {
+            let result = PathPattern::from_str("photos/[a");
+            assert!(
+                matches!(result, Err(ref e) if e.starts_with("Error: 'photos/[a' is not a valid glob pattern"))
+            );
+        }
+    }
+
+    mod time_zone_from_str {
+        use super::*;
+
+        #[test]
+        fn test_valid_local() {
+            assert_eq!(TimeZone::from_str("local"), Ok(TimeZone::Local));
+        }
+
+        #[test]
+        fn test_valid_utc() {
+            assert_eq!(TimeZone::from_str("utc"), Ok(TimeZone::Offset(0)));
+        }
+
+        #[test]
+        fn test_valid_positive_offset() {
+            assert_eq!(TimeZone::from_str("+05:30"), Ok(TimeZone::Offset(330)));
+        }
+
+        #[test]
+        fn test_valid_negative_offset() {
+            assert_eq!(TimeZone::from_str("-08:00"), Ok(TimeZone::Offset(-480)));
+        }
+
+        #[test]
+        fn test_missing_sign() {
+            let result = TimeZone::from_str("02:00");
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err(),
+                "Error: '02:00' is not a valid time zone. Valid time zones: local, utc or an offset like +02:00"
+            );
+        }
+
+        #[test]
+        fn test_out_of_range() {
+            assert!(TimeZone::from_str("+15:00").is_err());
+            assert!(TimeZone::from_str("+02:60").is_err());
+        }
+
+        #[test]
+        fn test_invalid_value() {
+            assert!(TimeZone::from_str("Europe/Kyiv").is_err());
+            assert!(TimeZone::from_str("+2:00").is_err());
+            assert!(TimeZone::from_str("+-1:00").is_err());
+            assert!(TimeZone::from_str("").is_err());
+        }
+    }
+
+    mod parse_date {
+        use super::*;
+
+        #[test]
+        fn test_valid() {
+            assert_eq!(
+                parse_date("2025-06-01"),
+                Ok(NaiveDate::from_ymd_opt(2025, 6, 1).unwrap())
+            );
+        }
+
+        #[test]
+        fn test_invalid() {
+            for s in ["2025-02-30", "01.06.2025", "2025-06"] {
+                assert_eq!(
+                    parse_date(s),
+                    Err(format!(
+                        "Error: '{}' is not a valid date. Valid dates look like 2025-06-01",
+                        s
+                    ))
+                );
+            }
+        }
+    }
+
+    mod clock_correction_from_str {
+        use super::*;
+
+        #[test]
+        fn test_valid_with_range() {
+            assert_eq!(
+                ClockCorrection::from_str("Canon, Canon EOS R6, *, +01:12, 2025-06-01, 2025-06-14"),
+                Ok(ClockCorrection {
+                    make: Some("Canon".to_string()),
+                    model: Some("Canon EOS R6".to_string()),
+                    serial: None,
+                    shift: 4320,
+                    from: NaiveDate::from_ymd_opt(2025, 6, 1),
+                    until: NaiveDate::from_ymd_opt(2025, 6, 14),
+                })
+            );
+        }
+
+        #[test]
+        fn test_valid_serial_only_with_seconds() {
+            let correction = ClockCorrection::from_str(",,032021001234,-00:01:30").unwrap();
+            assert_eq!(correction.make, None);
+            assert_eq!(correction.serial, Some("032021001234".to_string()));
+            assert_eq!(correction.shift, -90);
+            assert_eq!(correction.from, None);
+        }
+
+        #[test]
+        fn test_open_ended_range() {
+            let correction = ClockCorrection::from_str("Sony,,,+00:30,2025-06-01,").unwrap();
+            assert_eq!(correction.from, NaiveDate::from_ymd_opt(2025, 6, 1));
+            assert_eq!(correction.until, None);
+        }
+
+        #[test]
+        fn test_wrong_field_count() {
+            let result = ClockCorrection::from_str("Canon,+01:00");
+            assert_eq!(
+                result.unwrap_err(),
+                "Error: 'Canon,+01:00' is not a valid clock correction. Expected make,model,serial,offset[,from,until]"
+            );
+        }
+
+        #[test]
+        fn test_invalid_offset() {
+            let result = ClockCorrection::from_str("Canon,,,01:12");
+            assert_eq!(
+                result.unwrap_err(),
+                "Error: '01:12' is not a valid clock offset. Valid offsets look like +01:12 or -00:00:45"
+            );
+            assert!(ClockCorrection::from_str("Canon,,,+1:12").is_err());
+            assert!(ClockCorrection::from_str("Canon,,,+01:60").is_err());
+        }
+
+        #[test]
+        fn test_invalid_range() {
+            assert!(ClockCorrection::from_str("Canon,,,+01:00,2025-06-31,").is_err());
+            assert!(ClockCorrection::from_str("Canon,,,+01:00,2025-06-14,2025-06-01").is_err());
+        }
+
+        #[test]
+        fn test_parse_table() {
+            let table =
+                "# make,model,serial,offset,from,until\n\nCanon,,,+01:12\n  Nikon,,,-00:05  \n";
+            let corrections = ClockCorrection::parse_table(table).unwrap();
+            assert_eq!(corrections.len(), 2);
+            assert_eq!(corrections[1].shift, -300);
+
+            assert_eq!(
+                ClockCorrection::parse_table("Canon,,,+01:12\nbroken").unwrap_err(),
+                "Error: 'broken' is not a valid clock correction. Expected make,model,serial,offset[,from,until] (clock corrections line 2)"
+            );
+        }
+    }
 }
@@ -0,0 +1,255 @@
+use crate::metadata::is_valid_datetime;
+use crate::raw::read_jpeg_xmp;
+use crate::types::PhotoDateTime;
+use exif::DateTime;
+use memchr::memmem;
+use regex::Regex;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// XMP date properties in priority order
+const DATE_PROPERTIES: &[&str] = &[
+    "exif:DateTimeOriginal",
+    "photoshop:DateCreated",
+    "xmp:CreateDate",
+];
+
+/// How far into a non-JPEG file we look for an embedded XMP packet
+const MAX_EMBEDDED_SCAN: u64 = 256 * 1024;
+
+static PROPERTY_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    DATE_PROPERTIES
+        .iter()
+        .map(|property| {
+            let property = regex::escape(property);
+            Regex::new(&format!(
+                r#"{0}\s*=\s*["']([^"']+)["']|<{0}>\s*([^<]+?)\s*</{0}>"#,
+                property
+            ))
+            .expect("property pattern should be valid")
+        })
+        .collect()
+});
+
+static XMP_DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(\d{4})-(\d{2})-(\d{2})(?:T(\d{2}):(\d{2})(?::(\d{2})(?:\.(\d{1,9}))?)?(Z|[+-]\d{2}:\d{2})?)?$",
+    )
+    .expect("XMP date pattern should be valid")
+});
+
+pub fn is_xmp_sidecar(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("xmp"))
+}
+
+/// Reads the capture date from an XMP sidecar (`photo.jpg.xmp` or `photo.xmp`),
+/// falling back to an XMP packet embedded in the file itself
+pub fn extract_xmp_datetime(path: &Path) -> Option<PhotoDateTime> {
+    if is_xmp_sidecar(path) {
+        return None;
+    }
+
+    sidecar_paths(path)
+        .iter()
+        .filter_map(|sidecar| fs::read_to_string(sidecar).ok())
+        .find_map(|xmp| datetime_from_xmp(&xmp))
+        .or_else(|| embedded_packet(path).and_then(|xmp| datetime_from_xmp(&xmp)))
+}
+
+fn sidecar_paths(path: &Path) -> Vec<PathBuf> {
+    let appended = |ext: &str| {
+        let mut name = OsString::from(path.as_os_str());
+        name.push(ext);
+        PathBuf::from(name)
+    };
+
+    vec![
+        appended(".xmp"),
+        appended(".XMP"),
+        path.with_extension("xmp"),
+        path.with_extension("XMP"),
+    ]
+}
+
+/// The XMP packet of a JPEG comes from its APP1 segment; other formats are searched
+/// within their first `MAX_EMBEDDED_SCAN` bytes
+fn embedded_packet(path: &Path) -> Option<String> {
+    let data = match read_jpeg_xmp(path).ok()? {
+        Some(data) => data,
+        None => {
+            let mut data = Vec::new();
+            File::open(path)
+                .ok()?
+                .take(MAX_EMBEDDED_SCAN)
+                .read_to_end(&mut data)
+                .ok()?;
+            data
+        }
+    };
+
+    let start = memmem::find(&data, b"<x:xmpmeta")?;
+    let end = start + memmem::find(&data[start..], b"</x:xmpmeta>")?;
+    Some(String::from_utf8_lossy(&data[start..end]).into_owned())
+}
+
+fn datetime_from_xmp(xmp: &str) -> Option<PhotoDateTime> {
+    PROPERTY_PATTERNS.iter().find_map(|pattern| {
+        pattern
+            .captures_iter(xmp)
+            .filter_map(|captures| captures.get(1).or_else(|| captures.get(2)))
+            .find_map(|value| parse_xmp_date(value.as_str()))
+    })
+}
+
+/// Parses an XMP (ISO 8601) date; dates without a time are placed at midnight
+fn parse_xmp_date(value: &str) -> Option<PhotoDateTime> {
+    let captures = XMP_DATE.captures(value.trim())?;
+    let number = |index: usize| -> Option<u32> {
+        match captures.get(index) {
+            Some(m) => m.as_str().parse().ok(),
+            None => Some(0),
+        }
+    };
+
+    let nanosecond = captures.get(7).map(|fraction| {
+        let digits = fraction.as_str();
+        digits.parse::<u32>().unwrap_or(0) * 10u32.pow(9 - digits.len() as u32)
+    });
+
+    let offset = captures.get(8).map(|tz| match tz.as_str() {
+        "Z" => 0,
+        tz => {
+            let sign = if tz.starts_with('-') { -1 } else { 1 };
+            let hours: i16 = tz[1..3].parse().unwrap_or(0);
+            let minutes: i16 = tz[4..6].parse().unwrap_or(0);
+            sign * (hours * 60 + minutes)
+        }
+    });
+
+    let dt = DateTime {
+        year: u16::try_from(number(1)?).ok()?,
+        month: u8::try_from(number(2)?).ok()?,
+        day: u8::try_from(number(3)?).ok()?,
+        hour: u8::try_from(number(4)?).ok()?,
+        minute: u8::try_from(number(5)?).ok()?,
+        second: u8::try_from(number(6)?).ok()?,
+        nanosecond,
+        offset,
+    };
+
+    is_valid_datetime(&dt).then(|| dt.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(dt: PhotoDateTime) -> String {
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02} {:?} {:?}",
+            dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second, dt.nanosecond, dt.offset
+        )
+    }
+
+    mod parse_xmp_date {
+        use super::*;
+
+        #[test]
+        fn test_full_with_offset() {
+            assert_eq!(
+                parse_xmp_date("2024-09-21T18:05:30+03:00").map(format),
+                Some("2024-09-21 18:05:30 None Some(180)".to_string())
+            );
+        }
+
+        #[test]
+        fn test_fraction_and_utc() {
+            assert_eq!(
+                parse_xmp_date("2024-09-21T18:05:30.25Z").map(format),
+                Some("2024-09-21 18:05:30 Some(250000000) Some(0)".to_string())
+            );
+        }
+
+        #[test]
+        fn test_without_seconds() {
+            assert_eq!(
+                parse_xmp_date("2024-09-21T18:05-04:30").map(format),
+                Some("2024-09-21 18:05:00 None Some(-270)".to_string())
+            );
+        }
+
+        #[test]
+        fn test_date_only() {
+            assert_eq!(
+                parse_xmp_date("2024-09-21").map(format),
+                Some("2024-09-21 00:00:00 None None".to_string())
+            );
+        }
+
+        #[test]
+        fn test_incomplete_or_invalid() {
+            assert!(parse_xmp_date("2024-09").is_none());
+            assert!(parse_xmp_date("2024-02-30T10:00:00").is_none());
+            assert!(parse_xmp_date("1969-12-31T10:00:00").is_none());
+            assert!(parse_xmp_date("21.09.2024").is_none());
+        }
+    }
+
+    mod datetime_from_xmp {
+        use super::*;
+
+        #[test]
+        fn test_attribute_form() {
+            let xmp = r#"<rdf:Description xmp:CreateDate="2024-09-22T10:00:00"
+                photoshop:DateCreated='2024-09-21T08:00:00'/>"#;
+            assert_eq!(
+                datetime_from_xmp(xmp).map(format),
+                Some("2024-09-21 08:00:00 None None".to_string())
+            );
+        }
+
+        #[test]
+        fn test_element_form_priority() {
+            let xmp = r#"<xmp:CreateDate>2024-09-22T10:00:00</xmp:CreateDate>
+                <exif:DateTimeOriginal>
+                    2024-09-21T18:05:30
+                </exif:DateTimeOriginal>"#;
+            assert_eq!(
+                datetime_from_xmp(xmp).map(format),
+                Some("2024-09-21 18:05:30 None None".to_string())
+            );
+        }
+
+        #[test]
+        fn test_invalid_value_falls_through() {
+            let xmp = r#"exif:DateTimeOriginal="unknown" xmp:CreateDate="2024-09-22""#;
+            assert_eq!(
+                datetime_from_xmp(xmp).map(format),
+                Some("2024-09-22 00:00:00 None None".to_string())
+            );
+        }
+
+        #[test]
+        fn test_no_dates() {
+            assert!(datetime_from_xmp(r#"<xmp:Rating>5</xmp:Rating>"#).is_none());
+        }
+    }
+
+    #[test]
+    fn test_sidecar_paths() {
+        let paths = sidecar_paths(Path::new("dir/IMG_0001.CR2"));
+        assert!(paths.contains(&PathBuf::from("dir/IMG_0001.CR2.xmp")));
+        assert!(paths.contains(&PathBuf::from("dir/IMG_0001.xmp")));
+    }
+
+    #[test]
+    fn test_is_xmp_sidecar() {
+        assert!(is_xmp_sidecar(Path::new("IMG_0001.xmp")));
+        assert!(is_xmp_sidecar(Path::new("IMG_0001.CR2.XMP")));
+        assert!(!is_xmp_sidecar(Path::new("IMG_0001.jpg")));
+    }
+}
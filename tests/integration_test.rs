@@ -1,7 +1,12 @@
+use chrono::NaiveDate;
 use chronophoto::processor::process;
-use chronophoto::types::{Action, Args, Mode};
+use chronophoto::types::{
+    Action, Args, ClockCorrection, DateTag, FileTime, FilenamePattern, Mode, RenameFormat,
+    TimeZone, XmpMode,
+};
 use std::fs;
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 use tempfile::{TempDir, tempdir};
 
 fn setup_dirs() -> (TempDir, TempDir) {
@@ -13,19 +18,17 @@ fn copy_fixture(fixture_name: &str, dest: PathBuf) {
     fs::copy(&src, dest).unwrap();
 }
 
+/// First line of the summary; the second one reports timing, which varies between runs
+fn counts(summary: String) -> String {
+    summary.lines().next().unwrap_or_default().to_string()
+}
+
 fn create_args(source: PathBuf, library: PathBuf) -> Args {
     // Create temp log file to suppress console output during tests
     let log_file = tempfile::NamedTempFile::new().unwrap();
     Args {
-        source,
-        library,
-        mode: Mode::Daily,
-        limit: 25,
-        rename: false,
-        action: Action::Move,
-        dry_run: false,
         log_file: Some(log_file.path().to_path_buf()),
-        verbose: false,
+        ..Args::new(vec![source], library)
     }
 }
 
@@ -48,8 +51,8 @@ fn test_daily_default() {
     let result = process(args);
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped (no EXIF), 0 failed"
+        counts(result.unwrap()),
+        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped, 0 failed"
     );
     assert!(fs::exists(temp_library.path().join("2025/06/15/photo1.jpg")).unwrap());
     assert!(fs::exists(temp_library.path().join("2025/01/01/photo2")).unwrap());
@@ -76,8 +79,8 @@ fn test_monthly() {
     let result = process(args);
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped (no EXIF), 0 failed"
+        counts(result.unwrap()),
+        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped, 0 failed"
     );
     assert!(fs::exists(temp_library.path().join("2025/06/photo1.jpg")).unwrap());
     assert!(fs::exists(temp_library.path().join("2025/01/photo2")).unwrap());
@@ -113,8 +116,8 @@ fn test_compact_with_limit() {
     let result = process(args);
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "Processed 5 files: 5 transferred, 0 were already organized, 0 skipped (no EXIF), 0 failed"
+        counts(result.unwrap()),
+        "Processed 5 files: 5 transferred, 0 were already organized, 0 skipped, 0 failed"
     );
     assert!(fs::exists(temp_library.path().join("2025/06/15/photo1.jpg")).unwrap());
     assert!(fs::exists(temp_library.path().join("2025/06/15/photo2.jpg")).unwrap());
@@ -123,6 +126,52 @@ fn test_compact_with_limit() {
     assert!(fs::exists(temp_library.path().join("2025/01/photo5")).unwrap());
 }
 
+#[test]
+fn test_compact_counts_across_directories() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    for (index, dir) in ["card1", "card2/DCIM", "card3"].iter().enumerate() {
+        fs::create_dir_all(temp_source.path().join(dir)).unwrap();
+        copy_fixture(
+            "photo_2025_06_15.jpg",
+            temp_source
+                .path()
+                .join(dir)
+                .join(format!("photo{}.jpg", index)),
+        );
+    }
+    copy_fixture(
+        "photo_no_extension",
+        temp_source.path().join("card3/other_month"),
+    );
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.mode = Mode::Compact;
+    args.limit = 2;
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 4 files: 4 transferred, 0 were already organized, 0 skipped, 0 failed"
+    );
+    // Three June photos in different folders exceed the limit together
+    for index in 0..3 {
+        assert!(
+            fs::exists(
+                temp_library
+                    .path()
+                    .join(format!("2025/06/15/photo{}.jpg", index))
+            )
+            .unwrap()
+        );
+    }
+    assert!(fs::exists(temp_library.path().join("2025/01/other_month")).unwrap());
+}
+
 #[test]
 fn test_flat() {
     let (temp_source, temp_library) = setup_dirs();
@@ -143,8 +192,8 @@ fn test_flat() {
     let result = process(args);
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped (no EXIF), 0 failed"
+        counts(result.unwrap()),
+        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped, 0 failed"
     );
     assert!(fs::exists(temp_library.path().join("photo1.jpg")).unwrap());
     assert!(fs::exists(temp_library.path().join("photo2")).unwrap());
@@ -168,8 +217,8 @@ fn test_rename() {
     let result = process(args);
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "Processed 1 files: 1 transferred, 0 were already organized, 0 skipped (no EXIF), 0 failed"
+        counts(result.unwrap()),
+        "Processed 1 files: 1 transferred, 0 were already organized, 0 skipped, 0 failed"
     );
     assert!(fs::exists(temp_library.path().join("2025/06/15/20250615_143000.jpg")).unwrap());
 }
@@ -192,8 +241,8 @@ fn test_copy() {
     let result = process(args);
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "Processed 1 files: 1 transferred, 0 were already organized, 0 skipped (no EXIF), 0 failed"
+        counts(result.unwrap()),
+        "Processed 1 files: 1 transferred, 0 were already organized, 0 skipped, 0 failed"
     );
     assert!(fs::exists(temp_library.path().join("2025/06/15/photo1.jpg")).unwrap());
     assert!(fs::exists(temp_source.path().join("photo1.jpg")).unwrap());
@@ -217,8 +266,8 @@ fn test_move() {
     let result = process(args);
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "Processed 1 files: 1 transferred, 0 were already organized, 0 skipped (no EXIF), 0 failed"
+        counts(result.unwrap()),
+        "Processed 1 files: 1 transferred, 0 were already organized, 0 skipped, 0 failed"
     );
     assert!(fs::exists(temp_library.path().join("2025/06/15/photo1.jpg")).unwrap());
     assert!(!fs::exists(temp_source.path().join("photo1.jpg")).unwrap());
@@ -242,8 +291,8 @@ fn test_dry_run() {
     let result = process(args);
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "[DRY RUN] Processed 1 files: 1 would be transferred, 0 were already organized, 0 skipped (no EXIF)"
+        counts(result.unwrap()),
+        "[DRY RUN] Processed 1 files: 1 would be transferred, 0 were already organized, 0 skipped"
     );
     assert!(!fs::exists(temp_library.path().join("2025/06/15/photo1.jpg")).unwrap());
     assert!(fs::exists(temp_source.path().join("photo1.jpg")).unwrap());
@@ -265,8 +314,8 @@ fn test_already_organized() {
     let result = process(args);
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "Processed 1 files: 1 transferred, 0 were already organized, 0 skipped (no EXIF), 0 failed"
+        counts(result.unwrap()),
+        "Processed 1 files: 1 transferred, 0 were already organized, 0 skipped, 0 failed"
     );
 
     let args = create_args(
@@ -277,8 +326,8 @@ fn test_already_organized() {
     let result = process(args);
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "Processed 1 files: 0 transferred, 1 were already organized, 0 skipped (no EXIF), 0 failed"
+        counts(result.unwrap()),
+        "Processed 1 files: 0 transferred, 1 were already organized, 0 skipped, 0 failed"
     );
 }
 
@@ -300,12 +349,512 @@ fn test_invalid_exif_data() {
     let result = process(args);
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "Processed 2 files: 0 transferred, 0 were already organized, 2 skipped (no EXIF), 0 failed"
+        counts(result.unwrap()),
+        "Processed 2 files: 0 transferred, 0 were already organized, 2 skipped (no date: 2), 0 failed"
     );
     assert!(fs::read_dir(temp_library.path()).unwrap().next().is_none());
 }
 
+#[test]
+fn test_skip_report() {
+    let (temp_source, temp_library) = setup_dirs();
+    let report_dir = tempfile::tempdir().unwrap();
+
+    copy_fixture("photo_2025_06_15.jpg", temp_source.path().join("dated.jpg"));
+    copy_fixture("photo_no_exif.jpg", temp_source.path().join("no_exif.jpg"));
+    fs::write(temp_source.path().join("broken.jpg"), "not an image").unwrap();
+    fs::write(temp_source.path().join("lonely, too.xmp"), "<x:xmpmeta/>").unwrap();
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.dry_run = true;
+    args.skip_report = Some(report_dir.path().join("skipped.csv"));
+
+    let result = process(args.clone());
+    assert_eq!(
+        counts(result.unwrap()),
+        "[DRY RUN] Processed 4 files: 1 would be transferred, 0 were already organized, 3 skipped (no date: 1, unreadable metadata: 1, orphan sidecar: 1)"
+    );
+
+    let source = temp_source.path().display();
+    assert_eq!(
+        fs::read_to_string(report_dir.path().join("skipped.csv")).unwrap(),
+        format!(
+            "path,reason,detail\n\
+             {source}/broken.jpg,unreadable,Unknown image format\n\
+             {source}/no_exif.jpg,no_date,No DateTime field in EXIF data\n\
+             \"{source}/lonely, too.xmp\",orphan_sidecar,Sidecar file without its photo\n"
+        )
+    );
+
+    args.skip_report = Some(report_dir.path().join("skipped.json"));
+    process(args).unwrap();
+    let json = fs::read_to_string(report_dir.path().join("skipped.json")).unwrap();
+    assert!(json.starts_with("[\n"));
+    assert!(json.contains(&format!(
+        "{{\"path\": \"{source}/no_exif.jpg\", \"reason\": \"no_date\", \"detail\": \"No DateTime field in EXIF data\"}},\n"
+    )));
+    assert_eq!(json.matches("\"reason\"").count(), 3);
+}
+
+#[test]
+fn test_quarantine_undated() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    fs::create_dir_all(temp_source.path().join("scans/old")).unwrap();
+    copy_fixture("photo_2025_06_15.jpg", temp_source.path().join("dated.jpg"));
+    copy_fixture(
+        "photo_no_exif.jpg",
+        temp_source.path().join("scans/old/page.jpg"),
+    );
+    fs::write(temp_source.path().join("scans/lonely.xmp"), "<x:xmpmeta/>").unwrap();
+    // Left by an earlier run
+    fs::create_dir_all(temp_library.path().join("_undated/scans/old")).unwrap();
+    copy_fixture(
+        "photo_no_exif.jpg",
+        temp_library.path().join("_undated/scans/old/page.jpg"),
+    );
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.quarantine = true;
+
+    args.dry_run = true;
+    assert_eq!(
+        counts(process(args.clone()).unwrap()),
+        "[DRY RUN] Processed 3 files: 1 would be transferred, 0 were already organized, 2 skipped (no date: 1, orphan sidecar: 1), 2 would be quarantined"
+    );
+    assert!(temp_source.path().join("scans/old/page.jpg").exists());
+
+    args.dry_run = false;
+    assert_eq!(
+        counts(process(args).unwrap()),
+        "Processed 3 files: 1 transferred, 0 were already organized, 2 skipped (no date: 1, orphan sidecar: 1), 2 quarantined, 0 failed"
+    );
+
+    let undated = temp_library.path().join("_undated/scans");
+    assert!(undated.join("lonely.xmp").exists());
+    assert!(undated.join("old/page.jpg").exists());
+    assert!(undated.join("old/page(1).jpg").exists());
+    assert!(temp_library.path().join("2025/06/15/dated.jpg").exists());
+    assert!(!temp_source.path().join("scans/old/page.jpg").exists());
+    assert!(!temp_source.path().join("scans/lonely.xmp").exists());
+}
+
+#[test]
+fn test_date_range() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture("photo_2025_06_15.jpg", temp_source.path().join("june.jpg"));
+    fs::write(temp_source.path().join("june.xmp"), "<x:xmpmeta/>").unwrap();
+    copy_fixture("video_2025_07_14.mp4", temp_source.path().join("july.mp4"));
+    copy_fixture("photo_no_exif.jpg", temp_source.path().join("undated.jpg"));
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.since = NaiveDate::from_ymd_opt(2025, 7, 1);
+    args.until = NaiveDate::from_ymd_opt(2025, 7, 31);
+
+    assert_eq!(
+        counts(process(args.clone()).unwrap()),
+        "Processed 4 files: 1 transferred, 0 were already organized, 2 outside the date range, 1 skipped (no date: 1), 0 failed (dated without EXIF: 1 from video metadata)"
+    );
+    assert!(temp_source.path().join("june.jpg").exists());
+    assert!(temp_source.path().join("june.xmp").exists());
+    assert!(!temp_source.path().join("july.mp4").exists());
+
+    args.since = NaiveDate::from_ymd_opt(2025, 6, 15);
+    args.until = NaiveDate::from_ymd_opt(2025, 6, 15);
+    assert_eq!(
+        counts(process(args).unwrap()),
+        "Processed 3 files: 2 transferred, 0 were already organized, 0 outside the date range, 1 skipped (no date: 1), 0 failed"
+    );
+    assert!(temp_library.path().join("2025/06/15/june.jpg").exists());
+    assert!(temp_library.path().join("2025/06/15/june.xmp").exists());
+}
+
+#[test]
+fn test_discovery_filters() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    fs::create_dir_all(temp_source.path().join("trip/edits")).unwrap();
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("trip/a.jpg"),
+    );
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("trip/edits/a.jpg"),
+    );
+    copy_fixture(
+        "photo_no_extension",
+        temp_source.path().join("trip/exported"),
+    );
+    copy_fixture("video_2025_07_14.mp4", temp_source.path().join("clip.mp4"));
+    for name in [".DS_Store", "Thumbs.db", "trip/notes.txt", "trip/scan.pdf"] {
+        fs::write(temp_source.path().join(name), "not a photo").unwrap();
+    }
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    // Without a list, only the default media extensions are read
+    args.dry_run = true;
+    args.exclude = vec!["edits".parse().unwrap()];
+    assert_eq!(
+        counts(process(args.clone()).unwrap()),
+        "[DRY RUN] Processed 3 files: 3 would be transferred, 0 were already organized, 0 skipped (dated without EXIF: 1 from video metadata)"
+    );
+
+    args.exclude_extensions = vec!["mp4".to_string()];
+    args.include = vec!["trip/*".parse().unwrap()];
+    assert_eq!(
+        counts(process(args.clone()).unwrap()),
+        "[DRY RUN] Processed 2 files: 2 would be transferred, 0 were already organized, 0 skipped"
+    );
+
+    // An empty extension list reads every file
+    let args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    assert_eq!(
+        counts(
+            process(Args {
+                dry_run: true,
+                extensions: Some(vec![]),
+                ..args
+            })
+            .unwrap()
+        ),
+        "[DRY RUN] Processed 8 files: 4 would be transferred, 0 were already organized, 4 skipped (unreadable metadata: 4) (dated without EXIF: 1 from video metadata)"
+    );
+}
+
+#[test]
+fn test_library_inside_source() {
+    let temp_source = tempdir().unwrap();
+    let library = temp_source.path().join("Library");
+
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("photo1.jpg"),
+    );
+    copy_fixture("photo_no_exif.jpg", temp_source.path().join("scan.jpg"));
+
+    let mut args = create_args(temp_source.path().to_path_buf(), library.clone());
+    args.quarantine = true;
+    assert_eq!(
+        counts(process(args.clone()).unwrap()),
+        "Processed 2 files: 1 transferred, 0 were already organized, 1 skipped (no date: 1), 1 quarantined, 0 failed"
+    );
+    assert!(library.join("2025/06/15/photo1.jpg").exists());
+    assert!(library.join("_undated/scan.jpg").exists());
+
+    // The organized files are not read again
+    assert_eq!(
+        counts(process(args).unwrap()),
+        "Processed 0 files: 0 transferred, 0 were already organized, 0 skipped, 0 quarantined, 0 failed"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_quarantine_with_relative_source() {
+    let temp_library = tempdir().unwrap();
+    copy_fixture("photo_no_exif.jpg", temp_library.path().join("scan.jpg"));
+
+    // The library given twice, once relative to the working directory
+    let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+    let library = temp_library.path().canonicalize().unwrap();
+    let mut source: PathBuf = cwd.components().skip(1).map(|_| "..").collect();
+    source.push(library.strip_prefix("/").unwrap());
+
+    let mut args = create_args(source, library.clone());
+    args.quarantine = true;
+    assert_eq!(
+        counts(process(args.clone()).unwrap()),
+        "Processed 1 files: 0 transferred, 0 were already organized, 1 skipped (no date: 1), 1 quarantined, 0 failed"
+    );
+    assert!(library.join("_undated/scan.jpg").exists());
+
+    // Already quarantined files stay where they are
+    assert_eq!(
+        counts(process(args).unwrap()),
+        "Processed 1 files: 0 transferred, 1 were already organized, 1 skipped (no date: 1), 0 quarantined, 0 failed"
+    );
+    assert!(library.join("_undated/scan.jpg").exists());
+    assert!(!library.join("_undated/_undated").exists());
+}
+
+#[test]
+fn test_source_inside_library() {
+    let temp_library = tempdir().unwrap();
+    let source = temp_library.path().join("2025/import");
+    fs::create_dir_all(&source).unwrap();
+    copy_fixture("photo_2025_06_15.jpg", source.join("photo1.jpg"));
+
+    let args = create_args(source.clone(), temp_library.path().to_path_buf());
+    let error = process(args).unwrap_err().to_string();
+    assert!(error.contains("is inside the library"), "{}", error);
+    assert!(source.join("photo1.jpg").exists());
+
+    // Reorganizing the whole library in place is fine
+    let mut args = create_args(
+        temp_library.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.quarantine = true;
+    assert_eq!(
+        counts(process(args).unwrap()),
+        "Processed 1 files: 1 transferred, 0 were already organized, 0 skipped, 0 quarantined, 0 failed"
+    );
+    assert!(temp_library.path().join("2025/06/15/photo1.jpg").exists());
+}
+
+#[test]
+fn test_multiple_sources() {
+    let (card1, card2) = setup_dirs();
+    let (phone, temp_library) = setup_dirs();
+    copy_fixture("photo_2025_06_15.jpg", card1.path().join("photo.jpg"));
+    copy_fixture("photo_2025_06_15.jpg", card2.path().join("photo.jpg"));
+    fs::create_dir_all(phone.path().join("DCIM")).unwrap();
+    copy_fixture("photo_2025_06_15.jpg", phone.path().join("DCIM/phone.jpg"));
+    copy_fixture("photo_no_extension", phone.path().join("DCIM/other_month"));
+
+    let mut args = create_args(
+        card1.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.sources.push(card2.path().to_path_buf());
+    args.sources.push(phone.path().to_path_buf());
+    args.mode = Mode::Compact;
+    args.limit = 2;
+
+    assert_eq!(
+        counts(process(args).unwrap()),
+        "Processed 4 files: 4 transferred, 0 were already organized, 0 skipped, 0 failed"
+    );
+    // Three June photos from different sources exceed the limit together, and same-named
+    // files are numbered in the order their sources were given
+    let day = temp_library.path().join("2025/06/15");
+    assert!(day.join("phone.jpg").exists());
+    assert!(day.join("photo.jpg").exists());
+    assert!(day.join("photo(1).jpg").exists());
+    assert!(fs::exists(temp_library.path().join("2025/01/other_month")).unwrap());
+    assert!(!card1.path().join("photo.jpg").exists());
+    assert!(!card2.path().join("photo.jpg").exists());
+}
+
+#[test]
+fn test_multiple_sources_keep_pairs_apart() {
+    let (card1, card2) = setup_dirs();
+    let temp_library = tempdir().unwrap();
+    // Two bodies with overlapping counters: a pair on each card, plus an unrelated JPEG
+    // sharing the stem of a RAW file on the other card
+    fs::create_dir_all(card1.path().join("DCIM")).unwrap();
+    fs::create_dir_all(card2.path().join("DCIM")).unwrap();
+    copy_fixture("raw_nikon.nef", card1.path().join("DCIM/DSC_0001.NEF"));
+    copy_fixture("photo_no_exif.jpg", card1.path().join("DCIM/DSC_0001.JPG"));
+    copy_fixture("raw_nikon.nef", card2.path().join("DCIM/DSC_0001.NEF"));
+    copy_fixture("photo_no_exif.jpg", card2.path().join("DCIM/DSC_0001.JPG"));
+    copy_fixture("raw_nikon.nef", card1.path().join("DCIM/DSC_0002.NEF"));
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        card2.path().join("DCIM/DSC_0002.JPG"),
+    );
+
+    let mut args = create_args(
+        card1.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.sources.push(card2.path().to_path_buf());
+
+    assert_eq!(
+        counts(process(args).unwrap()),
+        "Processed 6 files: 6 transferred, 0 were already organized, 0 skipped, 0 failed"
+    );
+    // Each JPEG without EXIF is dated from the RAW file on its own card
+    let day = temp_library.path().join("2025/03/01");
+    for name in [
+        "DSC_0001.NEF",
+        "DSC_0001.JPG",
+        "DSC_0001(1).NEF",
+        "DSC_0001(1).JPG",
+        "DSC_0002.NEF",
+    ] {
+        assert!(day.join(name).exists(), "{}", name);
+    }
+    assert!(temp_library.path().join("2025/06/15/DSC_0002.JPG").exists());
+}
+
+#[test]
+fn test_overlapping_sources() {
+    let (temp_source, temp_library) = setup_dirs();
+    fs::create_dir_all(temp_source.path().join("DCIM")).unwrap();
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("DCIM/photo1.jpg"),
+    );
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.sources.push(temp_source.path().join("DCIM"));
+    let error = process(args).unwrap_err().to_string();
+    assert!(error.contains("overlap"), "{}", error);
+    assert!(temp_source.path().join("DCIM/photo1.jpg").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_move_followed_symlink() {
+    let (temp_source, temp_library) = setup_dirs();
+    let real = tempdir().unwrap();
+    copy_fixture("photo_2025_06_15.jpg", real.path().join("IMG.jpg"));
+    std::os::unix::fs::symlink(
+        real.path().join("IMG.jpg"),
+        temp_source.path().join("IMG.jpg"),
+    )
+    .unwrap();
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.follow_symlinks = true;
+
+    assert_eq!(
+        counts(process(args).unwrap()),
+        "Processed 1 files: 1 transferred, 0 were already organized, 0 skipped, 0 failed"
+    );
+    // The photo itself lands in the library and only the link is removed
+    let organized = temp_library.path().join("2025/06/15/IMG.jpg");
+    assert!(!organized.symlink_metadata().unwrap().is_symlink());
+    assert_eq!(
+        fs::read(&organized).unwrap(),
+        fs::read("tests/fixtures/photo_2025_06_15.jpg").unwrap()
+    );
+    assert!(
+        temp_source
+            .path()
+            .join("IMG.jpg")
+            .symlink_metadata()
+            .is_err()
+    );
+    assert!(real.path().join("IMG.jpg").exists());
+}
+
+#[test]
+fn test_reorganize_library_in_place() {
+    let temp_library = tempdir().unwrap();
+    fs::create_dir_all(temp_library.path().join("2025")).unwrap();
+    for index in 0..40 {
+        let dir = temp_library
+            .path()
+            .join(format!("1999 scans/d{:02}", index));
+        fs::create_dir_all(&dir).unwrap();
+        copy_fixture(
+            "photo_2025_06_15.jpg",
+            dir.join(format!("photo{}.jpg", index)),
+        );
+    }
+
+    let args = create_args(
+        temp_library.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    // Files moved into folders the walk has not reached yet are not read again
+    assert_eq!(
+        counts(process(args).unwrap()),
+        "Processed 40 files: 40 transferred, 0 were already organized, 0 skipped, 0 failed"
+    );
+    assert!(temp_library.path().join("2025/06/15/photo39.jpg").exists());
+}
+
+#[test]
+fn test_reorganize_library_in_place_into_existing_folder() {
+    let temp_library = tempdir().unwrap();
+    fs::create_dir_all(temp_library.path().join("2025/06/15")).unwrap();
+    for index in 0..40 {
+        let dir = temp_library
+            .path()
+            .join(format!("1999 scans/d{:02}", index));
+        fs::create_dir_all(&dir).unwrap();
+        copy_fixture(
+            "photo_2025_06_15.jpg",
+            dir.join(format!("photo{}.jpg", index)),
+        );
+    }
+
+    let args = create_args(
+        temp_library.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    // The day folder is listed after the scans, so the files moved into it are skipped
+    assert_eq!(
+        counts(process(args).unwrap()),
+        "Processed 40 files: 40 transferred, 0 were already organized, 0 skipped, 0 failed"
+    );
+    assert!(temp_library.path().join("2025/06/15/photo39.jpg").exists());
+}
+
+#[test]
+fn test_skip_report_invalid_path() {
+    let (temp_source, temp_library) = setup_dirs();
+    copy_fixture("photo_2025_06_15.jpg", temp_source.path().join("dated.jpg"));
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.skip_report = Some(temp_library.path().join("missing/skipped.csv"));
+
+    assert!(process(args).is_err());
+    assert!(temp_source.path().join("dated.jpg").exists());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_skip_report_write_fails() {
+    let (temp_source, temp_library) = setup_dirs();
+    // Enough rows to fill the report's buffer while files are still being moved
+    for index in 0..20 {
+        let dir = temp_source.path().join(format!("scans {:02}", index));
+        fs::create_dir_all(&dir).unwrap();
+        for scan in 0..10 {
+            copy_fixture(
+                "photo_no_exif.jpg",
+                dir.join(format!("scan with a long name {}.jpg", scan)),
+            );
+        }
+    }
+    let last = temp_source.path().join("scans 19/dated.jpg");
+    copy_fixture("photo_2025_06_15.jpg", last.clone());
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    // Opens fine, but every write fails for lack of space
+    args.skip_report = Some(PathBuf::from("/dev/full"));
+
+    assert_eq!(
+        counts(process(args).unwrap()),
+        "Processed 201 files: 1 transferred, 0 were already organized, 200 skipped (no date: 200), 0 failed"
+    );
+    assert!(!last.exists());
+    assert!(temp_library.path().join("2025/06/15/dated.jpg").exists());
+}
+
 #[test]
 fn test_name_conflict() {
     let (temp_source, temp_library) = setup_dirs();
@@ -328,8 +877,8 @@ fn test_name_conflict() {
     let result = process(args);
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped (no EXIF), 0 failed"
+        counts(result.unwrap()),
+        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped, 0 failed"
     );
     assert!(fs::exists(temp_library.path().join("2025/06/15/photo1.jpg")).unwrap());
     assert!(fs::exists(temp_library.path().join("2025/06/15/photo1(1).jpg")).unwrap());
@@ -357,8 +906,8 @@ fn test_filename_conflict_on_rename() {
 
     assert!(result.is_ok());
     assert_eq!(
-        result.unwrap(),
-        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped (no EXIF), 0 failed"
+        counts(result.unwrap()),
+        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped, 0 failed"
     );
     assert!(fs::exists(temp_library.path().join("2025/06/15/20250615_143000.jpg")).unwrap());
     assert!(
@@ -370,3 +919,744 @@ fn test_filename_conflict_on_rename() {
         .unwrap()
     );
 }
+
+#[test]
+fn test_prefers_date_time_original() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture("photo_edited.jpg", temp_source.path().join("photo1.jpg"));
+
+    let args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert!(fs::exists(temp_library.path().join("2025/06/10/photo1.jpg")).unwrap());
+}
+
+#[test]
+fn test_custom_date_tags_order() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture("photo_edited.jpg", temp_source.path().join("photo1.jpg"));
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("photo2.jpg"),
+    );
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.date_tags = vec![DateTag::Digitized, DateTag::Modified];
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped, 0 failed"
+    );
+    assert!(fs::exists(temp_library.path().join("2025/06/11/photo1.jpg")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2025/06/15/photo2.jpg")).unwrap());
+}
+
+#[test]
+fn test_camera_local_time() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture(
+        "photo_tokyo_offset.jpg",
+        temp_source.path().join("photo1.jpg"),
+    );
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.rename = true;
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert!(fs::exists(temp_library.path().join("2025/06/16/20250616_013000.jpg")).unwrap());
+}
+
+#[test]
+fn test_target_timezone() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture(
+        "photo_tokyo_offset.jpg",
+        temp_source.path().join("photo1.jpg"),
+    );
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("photo2.jpg"),
+    );
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.rename = true;
+    args.timezone = TimeZone::Offset(0);
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert!(fs::exists(temp_library.path().join("2025/06/15/20250615_163000.jpg")).unwrap());
+    // Photos without an offset keep their camera time
+    assert!(fs::exists(temp_library.path().join("2025/06/15/20250615_143000.jpg")).unwrap());
+}
+
+#[test]
+fn test_burst_conflicts_follow_shooting_order() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture("photo_burst_750.jpg", temp_source.path().join("a.jpg"));
+    copy_fixture("photo_burst_250.jpg", temp_source.path().join("b.jpg"));
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.rename = true;
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        fs::read(temp_library.path().join("2025/06/15/20250615_143000.jpg")).unwrap(),
+        fs::read("tests/fixtures/photo_burst_250.jpg").unwrap()
+    );
+    assert_eq!(
+        fs::read(
+            temp_library
+                .path()
+                .join("2025/06/15/20250615_143000(1).jpg")
+        )
+        .unwrap(),
+        fs::read("tests/fixtures/photo_burst_750.jpg").unwrap()
+    );
+}
+
+#[test]
+fn test_rename_with_milliseconds() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture("photo_burst_750.jpg", temp_source.path().join("a.jpg"));
+    copy_fixture("photo_burst_250.jpg", temp_source.path().join("b.jpg"));
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.rename = true;
+    args.rename_format = RenameFormat::Milliseconds;
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert!(
+        fs::exists(
+            temp_library
+                .path()
+                .join("2025/06/15/20250615_143000_250.jpg")
+        )
+        .unwrap()
+    );
+    assert!(
+        fs::exists(
+            temp_library
+                .path()
+                .join("2025/06/15/20250615_143000_750.jpg")
+        )
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_filename_dates_fallback() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture(
+        "photo_no_exif.jpg",
+        temp_source.path().join("IMG_20240301_101500.jpg"),
+    );
+    copy_fixture(
+        "photo_no_exif.jpg",
+        temp_source
+            .path()
+            .join("Screenshot_2024-03-02-08-00-00.png"),
+    );
+    copy_fixture("photo_no_exif.jpg", temp_source.path().join("DSC_0001.jpg"));
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("IMG_20240303_101500.jpg"),
+    );
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.filename_patterns = FilenamePattern::defaults();
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 4 files: 3 transferred, 0 were already organized, 1 skipped (no date: 1), 0 failed (dated without EXIF: 2 from file name)"
+    );
+    assert!(
+        fs::exists(
+            temp_library
+                .path()
+                .join("2024/03/01/IMG_20240301_101500.jpg")
+        )
+        .unwrap()
+    );
+    assert!(
+        fs::exists(
+            temp_library
+                .path()
+                .join("2024/03/02/Screenshot_2024-03-02-08-00-00.png")
+        )
+        .unwrap()
+    );
+    // EXIF still takes precedence over the file name
+    assert!(
+        fs::exists(
+            temp_library
+                .path()
+                .join("2025/06/15/IMG_20240303_101500.jpg")
+        )
+        .unwrap()
+    );
+    assert!(fs::exists(temp_source.path().join("DSC_0001.jpg")).unwrap());
+}
+
+#[test]
+fn test_filename_dates_disabled_by_default() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture(
+        "photo_no_exif.jpg",
+        temp_source.path().join("IMG_20240301_101500.jpg"),
+    );
+
+    let args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 1 files: 0 transferred, 0 were already organized, 1 skipped (no date: 1), 0 failed"
+    );
+}
+
+#[test]
+fn test_file_time_fallback() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture("photo_no_exif.jpg", temp_source.path().join("no_exif.jpg"));
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("photo1.jpg"),
+    );
+    // 2023-05-15 12:00:00 UTC, mid-month in every time zone
+    fs::File::options()
+        .write(true)
+        .open(temp_source.path().join("no_exif.jpg"))
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1_684_152_000))
+        .unwrap();
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.mode = Mode::Monthly;
+    args.file_time = Some(FileTime::Modified);
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped, 0 failed (dated without EXIF: 1 from file time)"
+    );
+    assert!(fs::exists(temp_library.path().join("_mtime/2023/05/no_exif.jpg")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2025/06/photo1.jpg")).unwrap());
+}
+
+#[test]
+fn test_videos() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture("video_2025_07_14.mp4", temp_source.path().join("clip.mp4"));
+    copy_fixture("video_apple.mov", temp_source.path().join("IMG_0001.MOV"));
+    copy_fixture("video_no_date.mp4", temp_source.path().join("broken.mp4"));
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.rename = true;
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 3 files: 2 transferred, 0 were already organized, 1 skipped (no date: 1), 0 failed (dated without EXIF: 2 from video metadata)"
+    );
+    // Apple creation date keeps the camera's local time
+    assert!(fs::exists(temp_library.path().join("2025/07/15/20250715_013000.MOV")).unwrap());
+    // mvhd is UTC noon, which stays on the same month in every time zone
+    let july = fs::read_dir(temp_library.path().join("2025/07")).unwrap();
+    let names: Vec<_> = july
+        .flat_map(|day| fs::read_dir(day.unwrap().path()).unwrap())
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names.len(), 2);
+    assert!(fs::exists(temp_source.path().join("broken.mp4")).unwrap());
+}
+
+#[test]
+fn test_live_photos() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    // Exported with a different name, linked by the content identifier
+    copy_fixture("live_photo.jpg", temp_source.path().join("IMG_4512.JPG"));
+    copy_fixture("live_photo.mov", temp_source.path().join("Live_4512.MOV"));
+    // No identifiers, linked by the shared stem
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("IMG_0100.JPG"),
+    );
+    copy_fixture("video_apple.mov", temp_source.path().join("IMG_0100.MOV"));
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.rename = true;
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 4 files: 4 transferred, 0 were already organized, 0 skipped, 0 failed"
+    );
+    assert!(fs::exists(temp_library.path().join("2025/07/15/20250715_013000.JPG")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2025/07/15/20250715_013000.MOV")).unwrap());
+    // The video follows the still's date, not its own
+    assert!(fs::exists(temp_library.path().join("2025/06/15/20250615_143000.JPG")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2025/06/15/20250615_143000.MOV")).unwrap());
+}
+
+fn takeout_json(title: &str, timestamp: u64) -> String {
+    format!(
+        r#"{{
+  "title": "{}",
+  "creationTime": {{ "timestamp": "1700000000", "formatted": "14 Nov 2023, 22:13:20 UTC" }},
+  "photoTakenTime": {{ "timestamp": "{}", "formatted": "" }}
+}}"#,
+        title, timestamp
+    )
+}
+
+#[test]
+fn test_google_takeout() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    let album = temp_source.path().join("Takeout/Google Photos/Trip");
+    fs::create_dir_all(&album).unwrap();
+    copy_fixture("photo_no_exif.jpg", album.join("IMG_1234.jpg"));
+    // 2019-06-28 12:00:00 UTC
+    fs::write(
+        album.join("IMG_1234.jpg.json"),
+        takeout_json("IMG_1234.jpg", 1_561_723_200),
+    )
+    .unwrap();
+    copy_fixture("photo_no_exif.jpg", album.join("IMG_1234(1).jpg"));
+    // 2019-06-29 12:00:00 UTC, with the counter moved behind the extension
+    fs::write(
+        album.join("IMG_1234.jpg(1).json"),
+        takeout_json("IMG_1234.jpg", 1_561_809_600),
+    )
+    .unwrap();
+    fs::write(album.join("metadata.json"), r#"{"title": "Trip"}"#).unwrap();
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.quarantine = true;
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 4 files: 4 transferred, 0 were already organized, 0 skipped, 0 quarantined, 0 failed (dated without EXIF: 2 from Google Takeout)"
+    );
+    assert!(fs::exists(temp_library.path().join("2019/06/28/IMG_1234.jpg")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2019/06/28/IMG_1234.jpg.json")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2019/06/29/IMG_1234(1).jpg")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2019/06/29/IMG_1234(1).json")).unwrap());
+    // Album metadata is neither a stray photo nor quarantined
+    assert!(fs::exists(album.join("metadata.json")).unwrap());
+    assert!(!fs::exists(temp_library.path().join("_undated")).unwrap());
+}
+
+#[test]
+fn test_clock_corrections() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    // Shot at 2025-08-02 00:22 by a body whose clock was 1h 12m behind
+    copy_fixture("photo_second_body.jpg", temp_source.path().join("late.jpg"));
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("main_body.jpg"),
+    );
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.rename = true;
+    args.clock_corrections = ClockCorrection::parse_table(
+        "# make,model,serial,offset,from,until\n\
+         Canon,Canon EOS R6,032021001234,+01:12,2025-07-25,2025-08-10\n",
+    )
+    .unwrap();
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped, 0 failed"
+    );
+    assert!(fs::exists(temp_library.path().join("2025/08/02/20250802_002200.jpg")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2025/06/15/20250615_143000.jpg")).unwrap());
+}
+
+#[test]
+fn test_parallel_extraction_is_deterministic() {
+    let fixture = fs::read("tests/fixtures/photo_2025_06_15.jpg").unwrap();
+    let organize = |threads: usize| {
+        let (temp_source, temp_library) = setup_dirs();
+        for burst in 0..8u8 {
+            // Same capture time, told apart by a byte after the end of the image
+            let mut photo = fixture.clone();
+            photo.push(burst);
+            fs::write(
+                temp_source.path().join(format!("burst_{}.jpg", burst)),
+                photo,
+            )
+            .unwrap();
+        }
+        copy_fixture("photo_no_exif.jpg", temp_source.path().join("no_exif.jpg"));
+
+        let mut args = create_args(
+            temp_source.path().to_path_buf(),
+            temp_library.path().to_path_buf(),
+        );
+        args.action = Action::Copy;
+        args.rename = true;
+        args.threads = threads;
+
+        let summary = process(args).unwrap();
+        // Which burst each organized name holds
+        let mut placed: Vec<(String, u8)> = fs::read_dir(temp_library.path().join("2025/06/15"))
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let burst = *fs::read(entry.path()).unwrap().last().unwrap();
+                (entry.file_name().into_string().unwrap(), burst)
+            })
+            .collect();
+        placed.sort();
+        (summary, placed)
+    };
+
+    let (sequential, sequential_placed) = organize(1);
+    let (parallel, parallel_placed) = organize(4);
+    assert_eq!(counts(sequential.clone()), counts(parallel.clone()));
+    for placed in [&sequential_placed, &parallel_placed] {
+        assert_eq!(placed.len(), 8);
+        assert!(placed.contains(&("20250615_143000.jpg".to_string(), 0)));
+        for burst in 1..8u8 {
+            let name = format!("20250615_143000({}).jpg", burst);
+            assert!(placed.contains(&(name, burst)), "{:?}", placed);
+        }
+    }
+
+    let throughput = parallel.lines().nth(1).unwrap();
+    assert!(throughput.starts_with("Read metadata of 9 files in "));
+    assert!(throughput.ends_with(" files/s, threads: 4)"));
+    assert!(sequential.ends_with(" files/s, threads: 1)"));
+}
+
+const SIDECAR_XMP: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:exif="http://ns.adobe.com/exif/1.0/"
+    exif:DateTimeOriginal="2023-11-05T07:45:00"/>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+#[test]
+fn test_xmp_fallback() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture("photo_no_exif.jpg", temp_source.path().join("sidecar.jpg"));
+    fs::write(temp_source.path().join("sidecar.jpg.xmp"), SIDECAR_XMP).unwrap();
+    copy_fixture(
+        "photo_embedded_xmp.jpg",
+        temp_source.path().join("embedded.jpg"),
+    );
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("native.jpg"),
+    );
+    fs::write(temp_source.path().join("native.xmp"), SIDECAR_XMP).unwrap();
+
+    let args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+
+    let result = process(args);
+    assert!(result.is_ok());
+    // Sidecars are not dated themselves, they travel with their photo
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 5 files: 5 transferred, 0 were already organized, 0 skipped, 0 failed (dated without EXIF: 2 from XMP)"
+    );
+    assert!(fs::exists(temp_library.path().join("2023/11/05/sidecar.jpg")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2023/11/05/sidecar.jpg.xmp")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2024/09/21/embedded.jpg")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2025/06/15/native.jpg")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2025/06/15/native.xmp")).unwrap());
+}
+
+#[test]
+fn test_sidecars_follow_renamed_photo() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    let day_dir = temp_library.path().join("2025/06/15");
+    fs::create_dir_all(&day_dir).unwrap();
+    fs::write(day_dir.join("20250615_143000.AAE"), "existing edit").unwrap();
+
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("IMG_0001.jpg"),
+    );
+    fs::write(temp_source.path().join("IMG_0001.AAE"), "edit").unwrap();
+    fs::write(temp_source.path().join("IMG_0001.jpg.pp3"), "profile").unwrap();
+    copy_fixture(
+        "video_2025_07_14.mp4",
+        temp_source.path().join("MVI_0002.mp4"),
+    );
+    fs::write(temp_source.path().join("MVI_0002.THM"), "thumbnail").unwrap();
+    fs::write(temp_source.path().join("orphan.xmp"), SIDECAR_XMP).unwrap();
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.rename = true;
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 6 files: 5 transferred, 0 were already organized, 1 skipped (orphan sidecar: 1), 0 failed (dated without EXIF: 1 from video metadata)"
+    );
+
+    // The photo takes the next free name because its edit file would have collided
+    assert!(!fs::exists(day_dir.join("20250615_143000.jpg")).unwrap());
+    assert!(fs::exists(day_dir.join("20250615_143000(1).jpg")).unwrap());
+    assert_eq!(
+        fs::read_to_string(day_dir.join("20250615_143000(1).AAE")).unwrap(),
+        "edit"
+    );
+    assert!(fs::exists(day_dir.join("20250615_143000(1).jpg.pp3")).unwrap());
+    assert_eq!(
+        fs::read_to_string(day_dir.join("20250615_143000.AAE")).unwrap(),
+        "existing edit"
+    );
+
+    // The video's day depends on the local time zone, its thumbnail keeps the same name
+    let video_day = fs::read_dir(temp_library.path().join("2025/07"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let mut names: Vec<_> = fs::read_dir(video_day)
+        .unwrap()
+        .map(|entry| PathBuf::from(entry.unwrap().file_name()))
+        .collect();
+    names.sort();
+    assert_eq!(names.len(), 2);
+    assert_eq!(names[0].with_extension(""), names[1].with_extension(""));
+    assert_eq!(names[1].extension().unwrap(), "mp4");
+    assert!(fs::exists(temp_source.path().join("orphan.xmp")).unwrap());
+}
+
+#[test]
+fn test_xmp_prefer() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("native.jpg"),
+    );
+    fs::write(temp_source.path().join("native.xmp"), SIDECAR_XMP).unwrap();
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.xmp = XmpMode::Prefer;
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert!(fs::exists(temp_library.path().join("2023/11/05/native.jpg")).unwrap());
+}
+
+#[test]
+fn test_xmp_ignore() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture(
+        "photo_embedded_xmp.jpg",
+        temp_source.path().join("embedded.jpg"),
+    );
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.xmp = XmpMode::Ignore;
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 1 files: 0 transferred, 0 were already organized, 1 skipped (no date: 1), 0 failed"
+    );
+}
+
+fn assert_raw_organized(fixture_name: &str, file_name: &str) {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture(fixture_name, temp_source.path().join(file_name));
+
+    let args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 1 files: 1 transferred, 0 were already organized, 0 skipped, 0 failed"
+    );
+    assert!(fs::exists(temp_library.path().join("2025/03/01").join(file_name)).unwrap());
+}
+
+#[test]
+fn test_raw_cr2() {
+    assert_raw_organized("raw_canon.cr2", "IMG_0001.CR2");
+}
+
+#[test]
+fn test_raw_cr3() {
+    assert_raw_organized("raw_canon.cr3", "IMG_0001.CR3");
+}
+
+#[test]
+fn test_raw_nef() {
+    assert_raw_organized("raw_nikon.nef", "DSC_0001.NEF");
+}
+
+#[test]
+fn test_raw_arw() {
+    assert_raw_organized("raw_sony.arw", "DSC00001.ARW");
+}
+
+#[test]
+fn test_raw_raf() {
+    assert_raw_organized("raw_fuji.raf", "DSCF0001.RAF");
+}
+
+#[test]
+fn test_raw_orf() {
+    assert_raw_organized("raw_olympus.orf", "P1010001.ORF");
+}
+
+#[test]
+fn test_raw_dng() {
+    assert_raw_organized("raw_adobe.dng", "PXL_0001.dng");
+}
+
+#[test]
+fn test_raw_jpeg_pair_shares_name_and_date() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    // The JPEG has no EXIF, so the pair is dated from the RAW file
+    copy_fixture("raw_nikon.nef", temp_source.path().join("DSC_0001.NEF"));
+    copy_fixture("photo_no_exif.jpg", temp_source.path().join("DSC_0001.JPG"));
+    // A JPEG-only name is already taken in the library
+    fs::create_dir_all(temp_library.path().join("2025/03")).unwrap();
+    fs::write(
+        temp_library.path().join("2025/03/20250301_101500.JPG"),
+        b"existing",
+    )
+    .unwrap();
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.mode = Mode::Compact;
+    args.limit = 1;
+    args.rename = true;
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "Processed 2 files: 2 transferred, 0 were already organized, 0 skipped, 0 failed"
+    );
+    // Counted once toward the compact limit, and renamed with one shared suffix
+    assert!(fs::exists(temp_library.path().join("2025/03/20250301_101500(1).NEF")).unwrap());
+    assert!(fs::exists(temp_library.path().join("2025/03/20250301_101500(1).JPG")).unwrap());
+    assert!(!fs::exists(temp_library.path().join("2025/03/20250301_101500.NEF")).unwrap());
+}
+
+#[test]
+fn test_raw_jpeg_pair_dry_run() {
+    let (temp_source, temp_library) = setup_dirs();
+
+    copy_fixture("photo_no_exif.jpg", temp_source.path().join("DSC_0001.NEF"));
+    copy_fixture(
+        "photo_2025_06_15.jpg",
+        temp_source.path().join("DSC_0001.JPG"),
+    );
+
+    let mut args = create_args(
+        temp_source.path().to_path_buf(),
+        temp_library.path().to_path_buf(),
+    );
+    args.dry_run = true;
+
+    let result = process(args);
+    assert!(result.is_ok());
+    assert_eq!(
+        counts(result.unwrap()),
+        "[DRY RUN] Processed 2 files: 2 would be transferred, 0 were already organized, 0 skipped"
+    );
+}